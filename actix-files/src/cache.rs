@@ -0,0 +1,276 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs::{self, File, Metadata},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::named::same_version;
+
+/// Paths recently found to be missing, remembered for a fixed time.
+///
+/// When full, the path recorded first is forgotten to make room.
+pub(crate) struct NegativeCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: RefCell<NegativeCacheInner>,
+}
+
+#[derive(Default)]
+struct NegativeCacheInner {
+    missing: HashMap<PathBuf, Instant>,
+    order: VecDeque<PathBuf>,
+}
+
+impl NegativeCacheInner {
+    fn remove(&mut self, path: &Path) {
+        self.missing.remove(path);
+        self.order.retain(|p| p != path);
+    }
+}
+
+impl NegativeCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: RefCell::default(),
+        }
+    }
+
+    /// Returns true if `path` was found missing less than the TTL ago.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        let mut inner = self.inner.borrow_mut();
+
+        match inner.missing.get(path) {
+            Some(recorded) if recorded.elapsed() < self.ttl => true,
+            Some(_) => {
+                inner.remove(path);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records that `path` is missing.
+    pub(crate) fn insert(&self, path: PathBuf) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.borrow_mut();
+
+        if let Some(recorded) = inner.missing.get_mut(&path) {
+            *recorded = Instant::now();
+            return;
+        }
+
+        while inner.missing.len() >= self.capacity {
+            match inner.order.pop_front() {
+                Some(oldest) => inner.missing.remove(&oldest),
+                None => break,
+            };
+        }
+
+        inner.order.push_back(path.clone());
+        inner.missing.insert(path, Instant::now());
+    }
+}
+
+/// Open handles of recently served files, so that popular files need not be opened again for
+/// each request.
+///
+/// Entries are checked against the file system at most once per interval and dropped if the file
+/// was modified or replaced. When full, the least recently used file is closed.
+pub(crate) struct OpenFileCache {
+    capacity: usize,
+    interval: Duration,
+    inner: RefCell<OpenFileCacheInner>,
+}
+
+struct CachedFile {
+    file: File,
+    md: Metadata,
+    checked: Instant,
+}
+
+#[derive(Default)]
+struct OpenFileCacheInner {
+    files: HashMap<PathBuf, CachedFile>,
+
+    // least recently used first
+    order: VecDeque<PathBuf>,
+}
+
+impl OpenFileCacheInner {
+    fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+        self.order.retain(|p| p != path);
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if let Some(pos) = self.order.iter().position(|p| p == path) {
+            if let Some(path) = self.order.remove(pos) {
+                self.order.push_back(path);
+            }
+        }
+    }
+}
+
+impl OpenFileCache {
+    pub(crate) fn new(capacity: usize, interval: Duration) -> Self {
+        Self {
+            capacity,
+            interval,
+            inner: RefCell::default(),
+        }
+    }
+
+    /// Returns a handle to the file at `path` and its metadata if the cache holds one that was
+    /// checked against the file system less than an interval ago.
+    pub(crate) fn get_fresh(&self, path: &Path) -> Option<(File, Metadata)> {
+        let mut inner = self.inner.borrow_mut();
+
+        let cached = inner.files.get(path)?;
+        if cached.checked.elapsed() >= self.interval {
+            return None;
+        }
+
+        let res = cached
+            .file
+            .try_clone()
+            .ok()
+            .map(|file| (file, cached.md.clone()));
+        inner.touch(path);
+        res
+    }
+
+    /// Returns a handle to the file at `path` and its metadata, from the cache if it still holds
+    /// the current version of the file.
+    ///
+    /// Handles returned for the same file share a cursor, so they must only be read at explicit
+    /// offsets.
+    pub(crate) fn open(&self, path: &Path) -> io::Result<(File, Metadata)> {
+        let mut inner = self.inner.borrow_mut();
+
+        if let Some(cached) = inner.files.get_mut(path) {
+            let fresh = if cached.checked.elapsed() < self.interval {
+                true
+            } else {
+                // the path is checked, not the handle, to notice files replaced by renaming
+                match fs::metadata(path) {
+                    Ok(md) if same_version(&md, &cached.md) => {
+                        cached.checked = Instant::now();
+                        true
+                    }
+                    _ => false,
+                }
+            };
+
+            if fresh {
+                let res = cached
+                    .file
+                    .try_clone()
+                    .map(|file| (file, cached.md.clone()));
+                inner.touch(path);
+                return res;
+            }
+
+            inner.remove(path);
+        }
+
+        let file = File::open(path)?;
+        let md = file.metadata()?;
+
+        if self.capacity > 0 {
+            while inner.files.len() >= self.capacity {
+                match inner.order.pop_front() {
+                    Some(oldest) => inner.files.remove(&oldest),
+                    None => break,
+                };
+            }
+
+            inner.order.push_back(path.to_owned());
+            inner.files.insert(
+                path.to_owned(),
+                CachedFile {
+                    file: file.try_clone()?,
+                    md: md.clone(),
+                    checked: Instant::now(),
+                },
+            );
+        }
+
+        Ok((file, md))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negative_cache() {
+        let cache = NegativeCache::new(2, Duration::from_secs(60));
+        cache.insert("a".into());
+        cache.insert("b".into());
+        cache.insert("a".into());
+        assert!(cache.contains(Path::new("a")));
+        assert!(cache.contains(Path::new("b")));
+
+        // the first path recorded makes room
+        cache.insert("c".into());
+        assert!(!cache.contains(Path::new("a")));
+        assert!(cache.contains(Path::new("b")));
+        assert!(cache.contains(Path::new("c")));
+
+        let cache = NegativeCache::new(2, Duration::from_secs(0));
+        cache.insert("a".into());
+        assert!(!cache.contains(Path::new("a")));
+
+        let cache = NegativeCache::new(0, Duration::from_secs(60));
+        cache.insert("a".into());
+        assert!(!cache.contains(Path::new("a")));
+    }
+
+    #[test]
+    fn test_open_file_cache() {
+        let dir = std::env::temp_dir().join("actix-files-open-file-cache");
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+        for path in &[&a, &b, &c] {
+            fs::write(path, "data").unwrap();
+        }
+
+        let cache = OpenFileCache::new(2, Duration::from_secs(60));
+        cache.open(&a).unwrap();
+        cache.open(&b).unwrap();
+
+        // `a` was used more recently than `b`, which is closed to make room
+        cache.open(&a).unwrap();
+        cache.open(&c).unwrap();
+        let inner = cache.inner.borrow();
+        assert!(inner.files.contains_key(&a));
+        assert!(!inner.files.contains_key(&b));
+        assert!(inner.files.contains_key(&c));
+        drop(inner);
+
+        // changes are only noticed once the interval has passed
+        fs::write(&a, "changed").unwrap();
+        assert_eq!(cache.open(&a).unwrap().1.len(), 4);
+        assert_eq!(cache.get_fresh(&a).unwrap().1.len(), 4);
+        assert!(cache.get_fresh(&b).is_none());
+
+        let cache = OpenFileCache::new(2, Duration::from_secs(0));
+        assert_eq!(cache.open(&a).unwrap().1.len(), 7);
+        assert!(cache.get_fresh(&a).is_none());
+        fs::write(&a, "changed again").unwrap();
+        assert_eq!(cache.open(&a).unwrap().1.len(), 13);
+
+        fs::remove_file(&a).unwrap();
+        assert!(cache.open(&a).is_err());
+        assert!(cache.inner.borrow().files.is_empty());
+    }
+}
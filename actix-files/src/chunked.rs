@@ -1,12 +1,20 @@
 use std::{
-    cmp, fmt,
-    fs::File,
+    cmp,
+    collections::VecDeque,
+    fmt,
+    fs::{File, Metadata},
     future::Future,
-    io::{self, Read, Seek},
+    io::{self, Read, Seek, SeekFrom},
     pin::Pin,
+    rc::Rc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
 use actix_web::{
     error::{BlockingError, Error},
     rt::task::{spawn_blocking, JoinHandle},
@@ -14,28 +22,175 @@ use actix_web::{
 use bytes::Bytes;
 use futures_core::{ready, Stream};
 
-#[doc(hidden)]
-/// A helper created from a `std::fs::File` which reads the file
-/// chunk-by-chunk on a `ThreadPool`.
+/// Default size of the buffer used for each chunk read from a file.
+pub(crate) const DEFAULT_BUFFER_SIZE: usize = 65_536;
+
+pub(crate) type BytesSentFn = dyn Fn(u64);
+
+/// Periodic check that a file being read has not been modified or replaced.
+pub(crate) struct ChangeCheck {
+    md: Metadata,
+    interval: Duration,
+    checked: Instant,
+}
+
+impl ChangeCheck {
+    /// Creates a check against `md` which is due every `interval`.
+    pub(crate) fn new(md: Metadata, interval: Duration) -> Self {
+        Self {
+            md,
+            interval,
+            checked: Instant::now(),
+        }
+    }
+
+    /// Returns the metadata to compare the file with if the check is due.
+    fn due(&mut self) -> Option<Metadata> {
+        if self.checked.elapsed() >= self.interval {
+            self.checked = Instant::now();
+            Some(self.md.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Fails if `file` no longer matches `md`.
+fn check_unchanged(file: &File, md: &Metadata) -> io::Result<()> {
+    let current = file.metadata()?;
+
+    // a file replaced by renaming another over it keeps its metadata but loses its last link
+    #[cfg(unix)]
+    let replaced = current.nlink() == 0;
+    #[cfg(not(unix))]
+    let replaced = false;
+
+    if replaced || !crate::named::same_version(&current, md) {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "file changed while being read",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// A readable and seekable source that can be moved to the blocking thread-pool.
+trait ReadSeek: Read + Seek + Send {}
+
+impl<R: Read + Seek + Send> ReadSeek for R {}
+
+/// Where chunks are read from.
+enum Source {
+    /// A file read at explicit offsets, so that several chunks can be read at once.
+    File(Arc<File>),
+
+    /// Any other reader, which is sought to each chunk in turn.
+    Reader(Arc<Mutex<dyn ReadSeek>>),
+}
+
+/// A stream of a region of a file, or of another reader, read chunk-by-chunk on the blocking
+/// thread-pool.
+///
+/// Only a bounded number of chunks are read ahead of the one taken from the stream, so a slow
+/// client does not cause the whole region to be buffered. By default, and always for readers, a
+/// chunk is read once the previous one has been taken; [`NamedFile::read_ahead`] allows several
+/// chunks of a file to be read concurrently. This is the stream [`NamedFile`](crate::NamedFile)
+/// bodies are read with.
+///
+/// [`NamedFile::read_ahead`]: crate::NamedFile::read_ahead
 pub struct ChunkedReadFile {
     size: u64,
     offset: u64,
-    state: ChunkedReadFileState,
+    source: Source,
+    pending: VecDeque<(usize, JoinHandle<Result<Bytes, io::Error>>)>,
+    requested: u64,
     counter: u64,
-}
-
-enum ChunkedReadFileState {
-    File(Option<File>),
-    Future(JoinHandle<Result<(File, Bytes), io::Error>>),
+    buffer_size: usize,
+    read_ahead: usize,
+    bytes_sent_fn: Option<Rc<BytesSentFn>>,
+    change_check: Option<ChangeCheck>,
 }
 
 impl ChunkedReadFile {
     pub(crate) fn new(size: u64, offset: u64, file: File) -> Self {
+        Self::with_source(size, offset, Source::File(Arc::new(file)))
+    }
+
+    /// Creates a stream of `size` bytes of `reader`, starting at `offset`.
+    ///
+    /// The reader is moved to the blocking thread-pool for each chunk, which is why it must be
+    /// `Send`. The stream ends with an error if the reader ends before `size` bytes were read.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use actix_files::ChunkedReadFile;
+    /// use actix_web::{dev::SizedStream, HttpResponse};
+    ///
+    /// let reader = Cursor::new(b"hello, world".to_vec());
+    /// let stream = ChunkedReadFile::from_reader(5, 7, reader);
+    /// let res = HttpResponse::Ok().body(SizedStream::new(5, stream));
+    /// ```
+    pub fn from_reader<R>(size: u64, offset: u64, reader: R) -> Self
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        Self::with_source(size, offset, Source::Reader(Arc::new(Mutex::new(reader))))
+    }
+
+    fn with_source(size: u64, offset: u64, source: Source) -> Self {
         Self {
             size,
             offset,
-            state: ChunkedReadFileState::File(Some(file)),
+            source,
+            pending: VecDeque::new(),
+            requested: 0,
             counter: 0,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            read_ahead: 0,
+            bytes_sent_fn: None,
+            change_check: None,
+        }
+    }
+
+    /// Sets the maximum number of bytes read for each chunk.
+    ///
+    /// Default is 64 KiB.
+    ///
+    /// # Panics
+    /// Panics if `buffer_size` is 0.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        assert_ne!(buffer_size, 0, "buffer size must be greater than 0");
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets the number of chunks read ahead of the one being sent.
+    pub(crate) fn read_ahead(mut self, chunks: usize) -> Self {
+        self.read_ahead = chunks;
+        self
+    }
+
+    /// Sets the function called with the number of bytes read so far after each chunk, and once
+    /// more when the stream is dropped.
+    pub(crate) fn bytes_sent_fn(mut self, bytes_sent_fn: Option<Rc<BytesSentFn>>) -> Self {
+        self.bytes_sent_fn = bytes_sent_fn;
+        self
+    }
+
+    /// Sets the check before reading a chunk that ends the stream with an error if the file was
+    /// changed.
+    pub(crate) fn change_check(mut self, change_check: Option<ChangeCheck>) -> Self {
+        self.change_check = change_check;
+        self
+    }
+}
+
+impl Drop for ChunkedReadFile {
+    fn drop(&mut self) {
+        if let Some(ref bytes_sent_fn) = self.bytes_sent_fn {
+            bytes_sent_fn(self.counter);
         }
     }
 }
@@ -49,50 +204,133 @@ impl fmt::Debug for ChunkedReadFile {
 impl Stream for ChunkedReadFile {
     type Item = Result<Bytes, Error>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.as_mut().get_mut();
-        match this.state {
-            ChunkedReadFileState::File(ref mut file) => {
-                let size = this.size;
-                let offset = this.offset;
-                let counter = this.counter;
-
-                if size == counter {
-                    Poll::Ready(None)
-                } else {
-                    let mut file = file
-                        .take()
-                        .expect("ChunkedReadFile polled after completion");
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
 
-                    let fut = spawn_blocking(move || {
-                        let max_bytes = cmp::min(size.saturating_sub(counter), 65_536) as usize;
+        // reads through a shared cursor can not overlap
+        let max_pending = match this.source {
+            Source::File(_) if cfg!(any(unix, windows)) => cmp::max(this.read_ahead, 1),
+            _ => 1,
+        };
 
-                        let mut buf = Vec::with_capacity(max_bytes);
-                        file.seek(io::SeekFrom::Start(offset))?;
+        while this.pending.len() < max_pending && this.requested < this.size {
+            let max_bytes =
+                cmp::min(this.size - this.requested, this.buffer_size as u64) as usize;
+            let offset = this.offset + this.requested;
+            let check_md = this.change_check.as_mut().and_then(ChangeCheck::due);
 
-                        let n_bytes =
-                            file.by_ref().take(max_bytes as u64).read_to_end(&mut buf)?;
+            let fut = match this.source {
+                Source::File(ref file) => {
+                    let file = Arc::clone(file);
 
-                        if n_bytes == 0 {
-                            return Err(io::ErrorKind::UnexpectedEof.into());
+                    spawn_blocking(move || {
+                        if let Some(ref md) = check_md {
+                            check_unchanged(&file, md)?;
                         }
 
-                        Ok((file, Bytes::from(buf)))
-                    });
-                    this.state = ChunkedReadFileState::Future(fut);
-                    self.poll_next(cx)
+                        read_chunk(&file, offset, max_bytes)
+                    })
+                }
+
+                Source::Reader(ref reader) => {
+                    let reader = Arc::clone(reader);
+
+                    spawn_blocking(move || {
+                        let mut reader = reader.lock().map_err(|_| {
+                            io::Error::new(io::ErrorKind::Other, "reader lock poisoned")
+                        })?;
+
+                        reader.seek(SeekFrom::Start(offset))?;
+                        read_chunk_with(max_bytes, |buf, _| reader.read(buf))
+                    })
                 }
-            }
-            ChunkedReadFileState::Future(ref mut fut) => {
-                let (file, bytes) =
-                    ready!(Pin::new(fut).poll(cx)).map_err(|_| BlockingError)??;
-                this.state = ChunkedReadFileState::File(Some(file));
+            };
+
+            this.pending.push_back((max_bytes, fut));
+            this.requested += max_bytes as u64;
+        }
+
+        let (max_bytes, fut) = match this.pending.front_mut() {
+            Some(pending) => pending,
+            None => return Poll::Ready(None),
+        };
 
-                this.offset += bytes.len() as u64;
-                this.counter += bytes.len() as u64;
+        let max_bytes = *max_bytes;
+        let res = ready!(Pin::new(fut).poll(cx));
+        this.pending.pop_front();
+        let bytes = res.map_err(|_| BlockingError)??;
 
-                Poll::Ready(Some(Ok(bytes)))
-            }
+        this.counter += bytes.len() as u64;
+
+        // the file was truncated; reads ahead were for the wrong offsets so the next read starts
+        // over from here, failing if nothing is left
+        if bytes.len() < max_bytes {
+            this.pending.clear();
+            this.requested = this.counter;
+        }
+
+        if let Some(ref bytes_sent_fn) = this.bytes_sent_fn {
+            bytes_sent_fn(this.counter);
         }
+
+        Poll::Ready(Some(Ok(bytes)))
     }
 }
+
+/// Reads up to `max_bytes` from `file` at `offset`, failing if there are none.
+pub(crate) fn read_chunk(file: &File, offset: u64, max_bytes: usize) -> io::Result<Bytes> {
+    read_chunk_with(max_bytes, |buf, n_bytes| {
+        read_at(file, buf, offset + n_bytes as u64)
+    })
+}
+
+/// Reads up to `max_bytes` with `read`, which is given the buffer to fill and the number of bytes
+/// read so far, failing if there are none.
+fn read_chunk_with<F>(max_bytes: usize, mut read: F) -> io::Result<Bytes>
+where
+    F: FnMut(&mut [u8], usize) -> io::Result<usize>,
+{
+    let mut buf = vec![0; max_bytes];
+    let mut n_bytes = 0;
+
+    while n_bytes < max_bytes {
+        match read(&mut buf[n_bytes..], n_bytes) {
+            Ok(0) => break,
+            Ok(n) => n_bytes += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    if n_bytes == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    buf.truncate(n_bytes);
+    Ok(Bytes::from(buf))
+}
+
+/// Reads from `file` at `offset` without relying on the file cursor, so handles that share one
+/// (e.g. from [`File::try_clone`]) can be read concurrently.
+#[cfg(unix)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Reads from `file` at `offset`.
+///
+/// This moves the file cursor but `seek_read` performs the positioning and read in one call, so
+/// handles that share a cursor can still be read concurrently.
+#[cfg(windows)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+/// Reads from `file` at `offset` by seeking first.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::io::{Read, Seek};
+
+    file.seek(io::SeekFrom::Start(offset))?;
+    file.read(buf)
+}
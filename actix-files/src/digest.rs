@@ -0,0 +1,290 @@
+use std::{cell::RefCell, cmp, fs::File, io, path::PathBuf, time::SystemTime};
+
+use actix_web::{http::ContentEncoding, rt, web};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::chunked::{read_at, DEFAULT_BUFFER_SIZE};
+
+/// Hash algorithms for the `Repr-Digest` header sent with a [`NamedFile`](crate::NamedFile).
+///
+/// See [`NamedFile::with_digest`](crate::NamedFile::with_digest).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgo {
+    /// SHA-256, sent as `sha-256`.
+    Sha256,
+
+    /// SHA-512, sent as `sha-512`.
+    Sha512,
+}
+
+impl DigestAlgo {
+    /// Returns the algorithm's key in a `Repr-Digest` header.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            DigestAlgo::Sha256 => "sha-256",
+            DigestAlgo::Sha512 => "sha-512",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        if key.eq_ignore_ascii_case("sha-256") {
+            Some(DigestAlgo::Sha256)
+        } else if key.eq_ignore_ascii_case("sha-512") {
+            Some(DigestAlgo::Sha512)
+        } else {
+            None
+        }
+    }
+}
+
+/// When a digest is sent and how its algorithm is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DigestMode {
+    /// Sent with every response, computed with the given algorithm.
+    Always(DigestAlgo),
+
+    /// Sent when requested, computed with the algorithm the client prefers.
+    Requested,
+}
+
+/// Returns the supported algorithm most preferred by a `Want-Repr-Digest` or `Want-Digest`
+/// header value, if any is acceptable.
+///
+/// Preferences are read both as `Want-Repr-Digest` integers (`sha-256=5`, from 0 to 10) and as
+/// `Want-Digest` quality values (`sha-256;q=0.5`). Algorithms without a preference are fully
+/// preferred, ones with a preference of zero are not acceptable, and ties go to the algorithm
+/// listed first.
+pub(crate) fn preferred_algo(want: &str) -> Option<DigestAlgo> {
+    let mut preferred: Option<(DigestAlgo, f32)> = None;
+
+    for item in want.split(',') {
+        let mut parts = item.split(';');
+        let key = parts.next().unwrap_or("").trim();
+
+        let (key, weight) = match key.find('=') {
+            Some(idx) => match key[idx + 1..].trim().parse::<u8>() {
+                Ok(weight) if weight <= 10 => (key[..idx].trim(), f32::from(weight) / 10.0),
+                _ => continue,
+            },
+            None => {
+                let q = parts
+                    .map(str::trim)
+                    .find(|param| param.starts_with("q=") || param.starts_with("Q="))
+                    .map(|param| param[2..].trim().parse::<f32>());
+
+                match q {
+                    Some(Ok(q)) if (0.0..=1.0).contains(&q) => (key, q),
+                    Some(_) => continue,
+                    None => (key, 1.0),
+                }
+            }
+        };
+
+        if let Some(algo) = DigestAlgo::from_key(key) {
+            if weight > 0.0 && preferred.map_or(true, |(_, best)| weight > best) {
+                preferred = Some((algo, weight));
+            }
+        }
+    }
+
+    preferred.map(|(algo, _)| algo)
+}
+
+/// Number of digests kept by each worker thread.
+const DIGEST_CACHE_CAPACITY: usize = 256;
+
+thread_local! {
+    static DIGESTS: DigestCache = DigestCache::default();
+}
+
+/// The version of a file a digest is computed for.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DigestKey {
+    pub(crate) path: PathBuf,
+    pub(crate) encoding: Option<ContentEncoding>,
+    pub(crate) algo: DigestAlgo,
+    pub(crate) len: u64,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+impl DigestKey {
+    /// Returns true if `other` is a version of the same file hashed with the same algorithm.
+    fn same_file(&self, other: &DigestKey) -> bool {
+        self.path == other.path && self.encoding == other.encoding && self.algo == other.algo
+    }
+}
+
+/// The digests last computed on a worker thread, shared by every response it builds.
+#[derive(Debug, Default)]
+pub(crate) struct DigestCache {
+    // oldest first
+    entries: RefCell<Vec<(DigestKey, String)>>,
+
+    // versions being hashed on the blocking thread-pool
+    pending: RefCell<Vec<DigestKey>>,
+}
+
+impl DigestCache {
+    /// Returns the digest of the first `key.len` bytes of `file` if it has been computed on this
+    /// thread. Otherwise, the file is hashed on the blocking thread-pool and `None` is returned
+    /// until the digest is ready.
+    pub(crate) fn get_or_spawn(key: DigestKey, file: &File) -> io::Result<Option<String>> {
+        DIGESTS.with(|cache| {
+            if let Some(digest) = cache.cached(&key) {
+                return Ok(Some(digest));
+            }
+
+            if cache.pending.borrow().contains(&key) {
+                return Ok(None);
+            }
+
+            let file = file.try_clone()?;
+            cache.pending.borrow_mut().push(key.clone());
+
+            rt::spawn(async move {
+                let (algo, len) = (key.algo, key.len);
+                let res = web::block(move || hash_with(algo, &file, len)).await;
+
+                DIGESTS.with(|cache| {
+                    cache.pending.borrow_mut().retain(|pending| *pending != key);
+
+                    match res {
+                        Ok(Ok(digest)) => cache.insert(key, digest),
+                        Ok(Err(err)) => log::debug!("error hashing file: {}", err),
+                        Err(err) => log::debug!("error hashing file: {}", err),
+                    }
+                });
+            });
+
+            Ok(None)
+        })
+    }
+
+    fn cached(&self, key: &DigestKey) -> Option<String> {
+        self.entries
+            .borrow()
+            .iter()
+            .find(|(cached_key, _)| cached_key == key)
+            .map(|(_, digest)| digest.clone())
+    }
+
+    /// Caches `digest`, replacing that of another version of the file and evicting the oldest
+    /// digest once the cache is full.
+    fn insert(&self, key: DigestKey, digest: String) {
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|(cached_key, _)| !cached_key.same_file(&key));
+
+        if entries.len() >= DIGEST_CACHE_CAPACITY {
+            entries.remove(0);
+        }
+
+        entries.push((key, digest));
+    }
+}
+
+/// Hashes the first `len` bytes of `file` with `algo` and returns the base64 encoded digest.
+fn hash_with(algo: DigestAlgo, file: &File, len: u64) -> io::Result<String> {
+    match algo {
+        DigestAlgo::Sha256 => hash::<Sha256>(file, len),
+        DigestAlgo::Sha512 => hash::<Sha512>(file, len),
+    }
+}
+
+/// Hashes the first `len` bytes of `file` and returns the base64 encoded digest.
+fn hash<D: Digest>(file: &File, len: u64) -> io::Result<String> {
+    let mut hasher = D::new();
+    let mut buf = vec![0; DEFAULT_BUFFER_SIZE];
+    let mut offset = 0;
+
+    while offset < len {
+        let max_bytes = cmp::min(len - offset, buf.len() as u64) as usize;
+
+        match read_at(file, &mut buf[..max_bytes], offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                hasher.update(&buf[..n]);
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(base64::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let path = env::temp_dir().join("actix-files-digest-hash.txt");
+        fs::write(&path, "hello").unwrap();
+        let file = File::open(&path).unwrap();
+
+        assert_eq!(
+            hash_with(DigestAlgo::Sha256, &file, 5).unwrap(),
+            "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="
+        );
+        assert_eq!(
+            hash_with(DigestAlgo::Sha256, &file, 4).unwrap(),
+            base64::encode(Sha256::digest(b"hell"))
+        );
+        assert_eq!(
+            hash_with(DigestAlgo::Sha512, &file, 5).unwrap(),
+            base64::encode(Sha512::digest(b"hello"))
+        );
+
+        assert!(hash_with(DigestAlgo::Sha256, &file, 6).is_err());
+    }
+
+    #[test]
+    fn test_digest_cache() {
+        let key = |path: &str, len| DigestKey {
+            path: PathBuf::from(path),
+            encoding: None,
+            algo: DigestAlgo::Sha256,
+            len,
+            modified: None,
+        };
+
+        let cache = DigestCache::default();
+        cache.insert(key("a", 1), "a1".to_owned());
+        cache.insert(key("b", 1), "b1".to_owned());
+        assert_eq!(cache.cached(&key("a", 1)).unwrap(), "a1");
+
+        // a new version of a file replaces the old one
+        cache.insert(key("a", 2), "a2".to_owned());
+        assert!(cache.cached(&key("a", 1)).is_none());
+        assert_eq!(cache.cached(&key("a", 2)).unwrap(), "a2");
+        assert_eq!(cache.cached(&key("b", 1)).unwrap(), "b1");
+
+        // the oldest digest is evicted once the cache is full
+        for i in 0..DIGEST_CACHE_CAPACITY - 1 {
+            cache.insert(key(&i.to_string(), 1), String::new());
+        }
+        assert!(cache.cached(&key("b", 1)).is_none());
+        assert_eq!(cache.cached(&key("a", 2)).unwrap(), "a2");
+    }
+
+    #[test]
+    fn test_preferred_algo() {
+        use DigestAlgo::*;
+
+        assert_eq!(preferred_algo("sha-256"), Some(Sha256));
+        assert_eq!(preferred_algo("sha-512, sha-256"), Some(Sha512));
+        assert_eq!(preferred_algo("sha-256=3, sha-512=10"), Some(Sha512));
+        assert_eq!(preferred_algo("sha-256=10, sha-512=10"), Some(Sha256));
+        assert_eq!(preferred_algo("SHA-256;q=0.3, sha-512;q=1"), Some(Sha512));
+        assert_eq!(preferred_algo("md5, sha-512;q=0.1"), Some(Sha512));
+        assert_eq!(preferred_algo("unixsum=10, sha-256=1"), Some(Sha256));
+
+        assert_eq!(preferred_algo("sha-256=0, sha-512;q=0"), None);
+        assert_eq!(preferred_algo("sha-256=11, sha-512;q=2"), None);
+        assert_eq!(preferred_algo("md5, crc32c"), None);
+        assert_eq!(preferred_algo(""), None);
+    }
+}
@@ -1,8 +1,16 @@
-use std::{fmt::Write, fs::DirEntry, io, path::Path, path::PathBuf};
+use std::{
+    fmt::Write,
+    fs::{DirEntry, Metadata},
+    io,
+    path::Path,
+    path::PathBuf,
+    time::UNIX_EPOCH,
+};
 
 use actix_web::{dev::ServiceResponse, HttpRequest, HttpResponse};
 use askama_escape::{escape as escape_html_entity, Html};
 use percent_encoding::{utf8_percent_encode, CONTROLS};
+use serde::Serialize;
 
 /// A directory; responds with the generated directory listing.
 #[derive(Debug)]
@@ -35,6 +43,22 @@ impl Directory {
         }
         false
     }
+
+    /// Returns the visible entries of this directory along with their metadata.
+    fn visible_entries(&self) -> io::Result<Vec<(DirEntry, Metadata)>> {
+        let mut entries = Vec::new();
+
+        for entry in self.path.read_dir()? {
+            if self.is_visible(&entry) {
+                let entry = entry.unwrap();
+                if let Ok(metadata) = entry.metadata() {
+                    entries.push((entry, metadata));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
 }
 
 pub(crate) type DirectoryRenderer =
@@ -62,35 +86,28 @@ pub(crate) fn directory_listing(
     let mut body = String::new();
     let base = Path::new(req.path());
 
-    for entry in dir.path.read_dir()? {
-        if dir.is_visible(&entry) {
-            let entry = entry.unwrap();
-            let p = match entry.path().strip_prefix(&dir.path) {
-                Ok(p) if cfg!(windows) => base.join(p).to_string_lossy().replace("\\", "/"),
-                Ok(p) => base.join(p).to_string_lossy().into_owned(),
-                Err(_) => continue,
-            };
-
-            // if file is a directory, add '/' to the end of the name
-            if let Ok(metadata) = entry.metadata() {
-                if metadata.is_dir() {
-                    let _ = write!(
-                        body,
-                        "<li><a href=\"{}\">{}/</a></li>",
-                        encode_file_url!(p),
-                        encode_file_name!(entry),
-                    );
-                } else {
-                    let _ = write!(
-                        body,
-                        "<li><a href=\"{}\">{}</a></li>",
-                        encode_file_url!(p),
-                        encode_file_name!(entry),
-                    );
-                }
-            } else {
-                continue;
-            }
+    for (entry, metadata) in dir.visible_entries()? {
+        let p = match entry.path().strip_prefix(&dir.path) {
+            Ok(p) if cfg!(windows) => base.join(p).to_string_lossy().replace("\\", "/"),
+            Ok(p) => base.join(p).to_string_lossy().into_owned(),
+            Err(_) => continue,
+        };
+
+        // if file is a directory, add '/' to the end of the name
+        if metadata.is_dir() {
+            let _ = write!(
+                body,
+                "<li><a href=\"{}\">{}/</a></li>",
+                encode_file_url!(p),
+                encode_file_name!(entry),
+            );
+        } else {
+            let _ = write!(
+                body,
+                "<li><a href=\"{}\">{}</a></li>",
+                encode_file_url!(p),
+                encode_file_name!(entry),
+            );
         }
     }
 
@@ -110,3 +127,42 @@ pub(crate) fn directory_listing(
             .body(html),
     ))
 }
+
+/// An entry of the JSON directory listing.
+#[derive(Serialize)]
+struct ListingEntry {
+    name: String,
+    size: u64,
+    is_dir: bool,
+    /// Modification time in seconds since the Unix epoch, if available.
+    modified: Option<u64>,
+}
+
+pub(crate) fn directory_listing_json(
+    dir: &Directory,
+    req: &HttpRequest,
+) -> Result<ServiceResponse, io::Error> {
+    let entries = dir
+        .visible_entries()?
+        .into_iter()
+        .map(|(entry, metadata)| ListingEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            size: metadata.len(),
+            is_dir: metadata.is_dir(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs()),
+        })
+        .collect::<Vec<_>>();
+
+    let body = serde_json::to_string(&entries)?;
+
+    Ok(ServiceResponse::new(
+        req.clone(),
+        HttpResponse::Ok()
+            .content_type(mime::APPLICATION_JSON)
+            .body(body),
+    ))
+}
@@ -1,6 +1,87 @@
+use std::cmp;
+
+use actix_web::http::{
+    header::{self, ContentEncoding},
+    HeaderMap,
+};
 use mime::Mime;
 
-/// Transforms MIME `text/*` types into their UTF-8 equivalent, if supported.
+/// Content encodings of precompressed file variants, in order of server preference, along with
+/// the extension appended to the original file name to find them.
+const PRECOMPRESSED_VARIANTS: &[(ContentEncoding, &str)] = &[
+    (ContentEncoding::Br, "br"),
+    (ContentEncoding::Zstd, "zst"),
+    (ContentEncoding::Gzip, "gz"),
+];
+
+/// Returns the precompressed variants acceptable to a request with `headers`, most preferred
+/// first.
+///
+/// Variants are ordered by the quality given to them in the request's `Accept-Encoding` header,
+/// falling back to server preference for equal qualities. Without an `Accept-Encoding` header, no
+/// variants are considered acceptable.
+pub(crate) fn precompressed_variants(
+    headers: &HeaderMap,
+) -> Vec<(ContentEncoding, &'static str)> {
+    let accept = match headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|val| val.to_str().ok())
+    {
+        Some(accept) => accept,
+        None => return Vec::new(),
+    };
+
+    // (content-coding, quality) pairs; items with malformed quality values are ignored
+    let accepted = accept
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+
+            let coding = params.next()?.trim();
+            if coding.is_empty() {
+                return None;
+            }
+
+            let quality = match params.find_map(|param| {
+                let param = param.trim();
+                param
+                    .strip_prefix("q=")
+                    .or_else(|| param.strip_prefix("Q="))
+            }) {
+                Some(quality) => quality.trim().parse::<f32>().ok()?,
+                None => 1.0,
+            };
+
+            Some((coding, quality))
+        })
+        .collect::<Vec<_>>();
+
+    let quality_of = |name: &str| {
+        accepted
+            .iter()
+            .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+            .map(|&(_, quality)| quality)
+    };
+
+    let mut variants = PRECOMPRESSED_VARIANTS
+        .iter()
+        .filter_map(|&(enc, ext)| {
+            let quality = quality_of(enc.as_str()).or_else(|| quality_of("*"))?;
+            Some((quality, enc, ext))
+        })
+        .filter(|&(quality, _, _)| quality > 0.0)
+        .collect::<Vec<_>>();
+
+    // stable sort keeps server preference for equal qualities
+    variants.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(cmp::Ordering::Equal));
+
+    variants
+        .into_iter()
+        .map(|(_, enc, ext)| (enc, ext))
+        .collect()
+}
+
+/// Adds a `charset` parameter to MIME types that carry text, if they have no parameters already.
 ///
 /// MIME types that are converted
 /// - application/javascript
@@ -9,44 +90,116 @@ use mime::Mime;
 /// - text/plain
 /// - text/csv
 /// - text/tab-separated-values
-pub(crate) fn equiv_utf8_text(ct: Mime) -> Mime {
-    // use (roughly) order of file-type popularity for a web server
+/// - text/javascript
+/// - application/json
+/// - application/xml
+/// - image/svg+xml
+pub(crate) fn with_charset(ct: Mime, charset: &str) -> Mime {
+    let is_text = matches!(
+        ct.essence_str(),
+        "application/javascript"
+            | "text/html"
+            | "text/css"
+            | "text/plain"
+            | "text/csv"
+            | "text/tab-separated-values"
+            | "text/javascript"
+            | "application/json"
+            | "application/xml"
+            | "image/svg+xml"
+    );
 
-    if ct == mime::APPLICATION_JAVASCRIPT {
-        return mime::APPLICATION_JAVASCRIPT_UTF_8;
+    if !is_text || ct.params().next().is_some() {
+        return ct;
     }
 
-    if ct == mime::TEXT_HTML {
-        return mime::TEXT_HTML_UTF_8;
-    }
+    format!("{}; charset={}", ct.essence_str(), charset)
+        .parse()
+        .unwrap_or(ct)
+}
 
-    if ct == mime::TEXT_CSS {
-        return mime::TEXT_CSS_UTF_8;
-    }
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
 
-    if ct == mime::TEXT_PLAIN {
-        return mime::TEXT_PLAIN_UTF_8;
-    }
+    use super::*;
 
-    if ct == mime::TEXT_CSV {
-        return mime::TEXT_CSV_UTF_8;
-    }
+    #[test]
+    fn test_precompressed_variants() {
+        let variants = |accept: Option<&str>| {
+            let mut req = TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header((header::ACCEPT_ENCODING, accept));
+            }
+            precompressed_variants(req.to_http_request().headers())
+                .into_iter()
+                .map(|(enc, _)| enc)
+                .collect::<Vec<_>>()
+        };
 
-    if ct == mime::TEXT_TAB_SEPARATED_VALUES {
-        return mime::TEXT_TAB_SEPARATED_VALUES_UTF_8;
+        assert_eq!(variants(None), vec![]);
+        assert_eq!(variants(Some("identity")), vec![]);
+        assert_eq!(
+            variants(Some("gzip, deflate, br")),
+            vec![ContentEncoding::Br, ContentEncoding::Gzip]
+        );
+        assert_eq!(
+            variants(Some("gzip, zstd, br")),
+            vec![
+                ContentEncoding::Br,
+                ContentEncoding::Zstd,
+                ContentEncoding::Gzip
+            ]
+        );
+        assert_eq!(
+            variants(Some("br;q=0.5, gzip")),
+            vec![ContentEncoding::Gzip, ContentEncoding::Br]
+        );
+        assert_eq!(
+            variants(Some("*, br;q=0")),
+            vec![ContentEncoding::Zstd, ContentEncoding::Gzip]
+        );
+        assert_eq!(variants(Some("GZIP")), vec![ContentEncoding::Gzip]);
     }
 
-    ct
-}
+    #[test]
+    fn test_with_charset() {
+        let utf8 = |ct| with_charset(ct, "utf-8");
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert_eq!(utf8(mime::TEXT_PLAIN), mime::TEXT_PLAIN_UTF_8);
+        assert_eq!(utf8(mime::TEXT_XML), mime::TEXT_XML);
+        assert_eq!(utf8(mime::IMAGE_PNG), mime::IMAGE_PNG);
+        assert_eq!(
+            utf8(mime::APPLICATION_OCTET_STREAM),
+            mime::APPLICATION_OCTET_STREAM
+        );
+        assert_eq!(
+            utf8(mime::APPLICATION_JAVASCRIPT),
+            mime::APPLICATION_JAVASCRIPT_UTF_8
+        );
+        assert_eq!(
+            utf8(mime::TEXT_JAVASCRIPT).to_string(),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(
+            utf8(mime::APPLICATION_JSON).to_string(),
+            "application/json; charset=utf-8"
+        );
+        assert_eq!(
+            utf8("application/xml".parse().unwrap()).to_string(),
+            "application/xml; charset=utf-8"
+        );
+        assert_eq!(
+            utf8(mime::IMAGE_SVG).to_string(),
+            "image/svg+xml; charset=utf-8"
+        );
+        assert_eq!(
+            with_charset(mime::TEXT_HTML, "ISO-8859-1").to_string(),
+            "text/html; charset=iso-8859-1"
+        );
 
-    #[test]
-    fn test_equiv_utf8_text() {
-        assert_eq!(equiv_utf8_text(mime::TEXT_PLAIN), mime::TEXT_PLAIN_UTF_8);
-        assert_eq!(equiv_utf8_text(mime::TEXT_XML), mime::TEXT_XML);
-        assert_eq!(equiv_utf8_text(mime::IMAGE_PNG), mime::IMAGE_PNG);
+        // an existing charset is left alone
+        let latin1: Mime = "application/json; charset=iso-8859-1".parse().unwrap();
+        assert_eq!(utf8(latin1.clone()), latin1);
     }
 }
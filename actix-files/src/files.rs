@@ -1,19 +1,34 @@
-use std::{cell::RefCell, fmt, io, path::PathBuf, rc::Rc};
+use std::{
+    cell::RefCell,
+    fmt, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
 
 use actix_service::{boxed, IntoServiceFactory, ServiceFactory, ServiceFactoryExt};
 use actix_utils::future::ok;
 use actix_web::{
-    dev::{AppService, HttpServiceFactory, ResourceDef, ServiceRequest, ServiceResponse},
+    dev::{
+        AppService, HttpServiceFactory, RequestHead, ResourceDef, ServiceRequest,
+        ServiceResponse,
+    },
     error::Error,
     guard::Guard,
-    http::header::DispositionType,
+    http::{
+        header::{self, DispositionType},
+        HeaderName, HeaderValue, StatusCode,
+    },
     HttpRequest,
 };
 use futures_core::future::LocalBoxFuture;
 
 use crate::{
-    directory_listing, named, Directory, DirectoryRenderer, FilesService, HttpNewService,
-    MimeOverride,
+    cache::{NegativeCache, OpenFileCache},
+    directory_listing, directory_listing_json, named,
+    service::Symlinks,
+    ContentTypeOverride, Directory, DirectoryRenderer, DispositionOverride, FilesService,
+    HttpNewService, PathFilter,
 };
 
 /// Static files handling service.
@@ -30,18 +45,34 @@ use crate::{
 pub struct Files {
     path: String,
     directory: PathBuf,
-    index: Option<String>,
+    index: Vec<String>,
     show_index: bool,
     redirect_to_slash: bool,
     default: Rc<RefCell<Option<Rc<HttpNewService>>>>,
     renderer: Rc<DirectoryRenderer>,
-    mime_override: Option<Rc<MimeOverride>>,
+    mime_override: Option<Rc<DispositionOverride>>,
+    content_type_override: Option<Rc<ContentTypeOverride>>,
     file_flags: named::Flags,
     use_guards: Option<Rc<dyn Guard>>,
     guards: Vec<Rc<dyn Guard>>,
     hidden_files: bool,
+    path_filter: Option<Rc<PathFilter>>,
+    symlinks: Symlinks,
+    cache_control: Option<header::CacheControl>,
+    precompressed: bool,
+    default_content_type: Option<mime::Mime>,
+    strip_prefix_segments: usize,
+    not_found_file: Option<PathBuf>,
+    response_headers: Vec<(HeaderName, HeaderValue)>,
+    directory_status: StatusCode,
+    negative_cache: Option<(usize, Duration)>,
+    open_file_cache: Option<usize>,
+    open_file_cache_interval: Duration,
 }
 
+/// Default time between checks for changes to files kept open for reuse.
+const DEFAULT_OPEN_FILE_CACHE_INTERVAL: Duration = Duration::from_secs(1);
+
 impl fmt::Debug for Files {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("Files")
@@ -60,9 +91,22 @@ impl Clone for Files {
             file_flags: self.file_flags,
             path: self.path.clone(),
             mime_override: self.mime_override.clone(),
+            content_type_override: self.content_type_override.clone(),
             use_guards: self.use_guards.clone(),
             guards: self.guards.clone(),
             hidden_files: self.hidden_files,
+            path_filter: self.path_filter.clone(),
+            symlinks: self.symlinks,
+            cache_control: self.cache_control.clone(),
+            precompressed: self.precompressed,
+            default_content_type: self.default_content_type.clone(),
+            strip_prefix_segments: self.strip_prefix_segments,
+            not_found_file: self.not_found_file.clone(),
+            response_headers: self.response_headers.clone(),
+            directory_status: self.directory_status,
+            negative_cache: self.negative_cache,
+            open_file_cache: self.open_file_cache,
+            open_file_cache_interval: self.open_file_cache_interval,
         }
     }
 }
@@ -98,16 +142,29 @@ impl Files {
         Files {
             path: mount_path.to_owned(),
             directory: dir,
-            index: None,
+            index: Vec::new(),
             show_index: false,
             redirect_to_slash: false,
             default: Rc::new(RefCell::new(None)),
             renderer: Rc::new(directory_listing),
             mime_override: None,
+            content_type_override: None,
             file_flags: named::Flags::default(),
             use_guards: None,
             guards: Vec::new(),
             hidden_files: false,
+            path_filter: None,
+            symlinks: Symlinks::Followed,
+            cache_control: None,
+            precompressed: false,
+            default_content_type: None,
+            strip_prefix_segments: 0,
+            not_found_file: None,
+            response_headers: Vec::new(),
+            directory_status: StatusCode::NOT_FOUND,
+            negative_cache: None,
+            open_file_cache: None,
+            open_file_cache_interval: DEFAULT_OPEN_FILE_CACHE_INTERVAL,
         }
     }
 
@@ -119,8 +176,33 @@ impl Files {
         self
     }
 
+    /// Set the status code of responses to directories that cannot be shown.
+    ///
+    /// Directories are shown through an [index file](Self::index_file) or a
+    /// [listing](Self::show_files_listing); without either, requests for them fail with this status
+    /// code. `403 Forbidden` signals that the directory exists but may not be browsed, while the
+    /// default `404 Not Found` does not reveal whether it exists.
+    pub fn directory_access_denied_status(mut self, status: StatusCode) -> Self {
+        self.directory_status = status;
+        self
+    }
+
+    /// Show files listing for directories as JSON.
+    ///
+    /// The listing is an array of objects with the `name`, `size`, `is_dir` and `modified` fields
+    /// of each entry, `modified` being in seconds since the Unix epoch or `null` if unavailable.
+    /// This replaces any renderer set with [`files_listing_renderer`](Self::files_listing_renderer).
+    pub fn show_files_listing_json(mut self) -> Self {
+        self.show_index = true;
+        self.renderer = Rc::new(directory_listing_json);
+        self
+    }
+
     /// Redirects to a slash-ended path when browsing a directory.
     ///
+    /// The redirect uses `308 Permanent Redirect` and keeps the query string. It is only issued
+    /// for directories that are served with an index file or a files listing.
+    ///
     /// By default never redirect.
     pub fn redirect_to_slash_directory(mut self) -> Self {
         self.redirect_to_slash = true;
@@ -128,6 +210,27 @@ impl Files {
     }
 
     /// Set custom directory renderer
+    ///
+    /// The renderer is called for directories when files listing is enabled with
+    /// [`show_files_listing`](Self::show_files_listing), in place of the built-in HTML listing.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::Files;
+    /// use actix_web::{dev::ServiceResponse, HttpResponse};
+    ///
+    /// let files = Files::new("/", "./static")
+    ///     .show_files_listing()
+    ///     .files_listing_renderer(|dir, req| {
+    ///         let count = dir.path.read_dir()?.filter(|entry| dir.is_visible(entry)).count();
+    ///         let body = format!("<p class=\"count\">{} entries</p>", count);
+    ///
+    ///         Ok(ServiceResponse::new(
+    ///             req.clone(),
+    ///             HttpResponse::Ok().content_type("text/html").body(body),
+    ///         ))
+    ///     });
+    /// ```
     pub fn files_listing_renderer<F>(mut self, f: F) -> Self
     where
         for<'r, 's> F:
@@ -137,7 +240,21 @@ impl Files {
         self
     }
 
+    /// Set the content type of files whose type cannot be guessed from their extension.
+    ///
+    /// This is used in place of `application/octet-stream` for files without an extension, such
+    /// as `LICENSE`, or with an unknown one. The disposition type is derived from it as well.
+    /// Types set by [`content_type_override`](Self::content_type_override) are not replaced.
+    pub fn default_content_type(mut self, content_type: mime::Mime) -> Self {
+        self.default_content_type = Some(content_type);
+        self
+    }
+
     /// Specifies mime override callback
+    ///
+    /// The callback is given the top-level type of each served file, such as `image`, and returns
+    /// the `Content-Disposition` type to send with it. It is applied after the content type has
+    /// been chosen.
     pub fn mime_override<F>(mut self, f: F) -> Self
     where
         F: Fn(&mime::Name<'_>) -> DispositionType + 'static,
@@ -146,12 +263,54 @@ impl Files {
         self
     }
 
+    /// Sets a callback choosing the content type of served files.
+    ///
+    /// The callback is given the path of each file on disk and returns the content type to serve
+    /// it with, or `None` to keep the type guessed from its extension. The disposition type is
+    /// derived from the returned type. An overridden type takes precedence over
+    /// [`default_content_type`](Self::default_content_type), which only applies to files the
+    /// callback returns `None` for.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static").content_type_override(|path| {
+    ///     match path.extension()?.to_str()? {
+    ///         "wasm" => Some("application/wasm".parse().unwrap()),
+    ///         _ => None,
+    ///     }
+    /// });
+    /// ```
+    pub fn content_type_override<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Path) -> Option<mime::Mime> + 'static,
+    {
+        self.content_type_override = Some(Rc::new(f));
+        self
+    }
+
     /// Set index file
     ///
     /// Shows specific index file for directory "/" instead of
     /// showing files listing.
+    ///
+    /// Directories without the index file are handled as if the file did not exist, even if
+    /// files listing is enabled.
+    ///
+    /// This is a shortcut for [`index_files`](Self::index_files) with a single entry.
     pub fn index_file<T: Into<String>>(mut self, index: T) -> Self {
-        self.index = Some(index.into());
+        self.index = vec![index.into()];
+        self
+    }
+
+    /// Set index files, in order of preference.
+    ///
+    /// When a directory is requested, the first of these that exists in it is served instead of
+    /// showing files listing. If none of them exist, the request is handled as if the index file
+    /// was not found.
+    pub fn index_files(mut self, indexes: &[&str]) -> Self {
+        self.index = indexes.iter().map(|&index| index.to_owned()).collect();
         self
     }
 
@@ -179,6 +338,44 @@ impl Files {
         self
     }
 
+    /// Set the `Cache-Control` header sent with every file served.
+    ///
+    /// See [`NamedFile::set_cache_control`](crate::NamedFile::set_cache_control).
+    pub fn cache_control(mut self, cache_control: header::CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Serve precompressed variants of files when the request accepts them.
+    ///
+    /// A `.br`, `.zst` or `.gz` sibling of the requested file is served in its place if the
+    /// `Accept-Encoding` header allows it, as with [`NamedFile::open_with_precompressed`].
+    ///
+    /// [`NamedFile::open_with_precompressed`]: crate::NamedFile::open_with_precompressed
+    pub fn prefer_precompressed(mut self) -> Self {
+        self.precompressed = true;
+        self
+    }
+
+    /// Ignore the first `n` segments of the path below the mount point when locating files.
+    ///
+    /// This allows serving versioned asset URLs without matching directories on disk. Requests
+    /// with no more than `n` segments get a 404 (or the default handler's) response.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_web::App;
+    /// use actix_files::Files;
+    ///
+    /// // `/assets/v123/app.js` is served from `./dist/app.js`
+    /// let app = App::new()
+    ///     .service(Files::new("/assets", "./dist").strip_prefix_segments(1));
+    /// ```
+    pub fn strip_prefix_segments(mut self, n: usize) -> Self {
+        self.strip_prefix_segments = n;
+        self
+    }
+
     /// Adds a routing guard.
     ///
     /// Use this to allow multiple chained file services that respond to strictly different
@@ -186,7 +383,7 @@ impl Files {
     /// request starts being handled by the file service, it will not be able to back-out and try
     /// the next service, you will simply get a 404 (or 405) error response.
     ///
-    /// To allow `POST` requests to retrieve files, see [`Files::use_guards`].
+    /// To allow `POST` requests to retrieve files, see [`Files::method_guard`].
     ///
     /// # Examples
     /// ```
@@ -206,8 +403,13 @@ impl Files {
     /// Specifies guard to check before fetching directory listings or files.
     ///
     /// Note that this guard has no effect on routing; it's main use is to guard on the request's
-    /// method just before serving the file, only allowing `GET` and `HEAD` requests by default.
-    /// See [`Files::guard`] for routing guards.
+    /// method just before serving the file, only allowing `GET`, `HEAD` and `OPTIONS` requests by
+    /// default. See [`Files::guard`] for routing guards.
+    ///
+    /// Without a method guard, `OPTIONS` requests are answered with `204 No Content` and other
+    /// methods with `405 Method Not Allowed`, both listing the accepted methods in an `Allow`
+    /// header. Requests rejected by a method guard get a `405 Method Not Allowed` response
+    /// without one.
     pub fn method_guard<G: Guard + 'static>(mut self, guard: G) -> Self {
         self.use_guards = Some(Rc::new(guard));
         self
@@ -230,6 +432,12 @@ impl Files {
 
     /// Sets default handler which is used when no matched file could be found.
     ///
+    /// The handler is called when the requested file cannot be opened, which includes requests
+    /// for directories whose index files are all missing. Directories served with an index file
+    /// or a files listing never reach it; a directory with neither is answered with an error
+    /// instead. Requests for hidden files or malformed paths are rejected before the handler is
+    /// considered.
+    ///
     /// # Examples
     /// Setting a fallback static file handler:
     /// ```
@@ -242,6 +450,18 @@ impl Files {
     /// # Ok(())
     /// # }
     /// ```
+    ///
+    /// Serving the shell of a single-page application for paths handled by client-side routing:
+    /// ```
+    /// use actix_files::{Files, NamedFile};
+    ///
+    /// # fn run() -> Result<(), actix_web::Error> {
+    /// let files = Files::new("/", "./dist")
+    ///     .index_file("index.html")
+    ///     .default_handler(NamedFile::open("./dist/index.html")?);
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn default_handler<F, U>(mut self, f: F) -> Self
     where
         F: IntoServiceFactory<U, ServiceRequest>,
@@ -260,11 +480,153 @@ impl Files {
         self
     }
 
+    /// Sets a file served with `404 Not Found` when no matching file could be found.
+    ///
+    /// This covers the same requests as [`default_handler`](Self::default_handler) when they fail
+    /// because a file does not exist, and takes precedence over it for those. Other errors, such as
+    /// missing permissions, still reach the default handler. The file is opened again for each
+    /// response; if it cannot be opened, the request is handled as if it was not set. It is sent
+    /// without `ETag` and `Last-Modified` headers and conditional request headers are ignored.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static").not_found_file("./static/404.html");
+    /// ```
+    pub fn not_found_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.not_found_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a header to every file and directory listing served.
+    ///
+    /// This can be called repeatedly to attach a fixed set of headers, such as security headers,
+    /// without a separate middleware. Headers the response already has, like `Content-Type` or
+    /// `Content-Length`, are not replaced; several values given for the same name are all sent.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_web::http::{header, HeaderValue};
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static")
+    ///     .add_response_header((
+    ///         header::CONTENT_SECURITY_POLICY,
+    ///         HeaderValue::from_static("default-src 'self'"),
+    ///     ))
+    ///     .add_response_header((
+    ///         header::X_CONTENT_TYPE_OPTIONS,
+    ///         HeaderValue::from_static("nosniff"),
+    ///     ));
+    /// ```
+    pub fn add_response_header(mut self, header: (HeaderName, HeaderValue)) -> Self {
+        self.response_headers.push(header);
+        self
+    }
+
+    /// Remembers paths found to be missing for `ttl`, up to `capacity` of them at a time.
+    ///
+    /// Requests for a remembered path are answered as not found without touching the file system,
+    /// which spares the disk when crawlers or scanners repeatedly ask for files that do not
+    /// exist. A file created at such a path is only served once `ttl` has passed. Each worker
+    /// keeps its own cache; when it is full, the path remembered first is forgotten.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static").negative_cache(1024, Duration::from_secs(5));
+    /// ```
+    pub fn negative_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.negative_cache = Some((capacity, ttl));
+        self
+    }
+
+    /// Keeps up to `capacity` recently served files open for reuse by later requests.
+    ///
+    /// Opening a file again for each request is wasted work for popular assets such as frontend
+    /// bundles. Cached files are checked for modification or replacement at most once every
+    /// [interval](Self::open_file_cache_interval), as is whether their path may still be served
+    /// (e.g. with [symlinks](Self::disable_symlinks) restricted), so a changed file may be served in
+    /// its old version until then. Each worker keeps its own cache; when it is full, the least recently
+    /// used file is closed. Files are not cached when serving
+    /// [precompressed](Self::prefer_precompressed) variants.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static").open_file_cache(64);
+    /// ```
+    pub fn open_file_cache(mut self, capacity: usize) -> Self {
+        self.open_file_cache = Some(capacity);
+        self
+    }
+
+    /// Set the time between checks that a file in the [open file cache](Self::open_file_cache)
+    /// has not changed.
+    ///
+    /// Default is 1 second.
+    pub fn open_file_cache_interval(mut self, interval: Duration) -> Self {
+        self.open_file_cache_interval = interval;
+        self
+    }
+
     /// Enables serving hidden files and directories, allowing a leading dots in url fragments.
+    ///
+    /// By default, requests with any path segment starting with a dot, such as `/.git/config`,
+    /// are handled as if the file did not exist.
     pub fn use_hidden_files(mut self) -> Self {
         self.hidden_files = true;
         self
     }
+
+    /// Disables following symlinks.
+    ///
+    /// By default, symlinks inside the served directory are followed wherever they point, so
+    /// anyone able to create a symlink in the directory can expose any file readable by the
+    /// server. With this set, paths that go through a symlink are handled as if the file did not
+    /// exist. See [`restrict_symlinks`](Self::restrict_symlinks) for still following symlinks that
+    /// stay inside the directory.
+    pub fn disable_symlinks(mut self) -> Self {
+        self.symlinks = Symlinks::Disabled;
+        self
+    }
+
+    /// Only follows symlinks that point to a location inside the served directory.
+    ///
+    /// With this set, paths that resolve to a location outside the served directory are handled
+    /// as if the file did not exist.
+    pub fn restrict_symlinks(mut self) -> Self {
+        self.symlinks = Symlinks::Contained;
+        self
+    }
+
+    /// Sets a predicate deciding which paths may be served.
+    ///
+    /// The predicate is given the requested path relative to the served directory, after it has
+    /// been decoded and checked for invalid segments. When it returns `false`, the request is
+    /// handled as if the file did not exist: the default handler is called if one is set and a
+    /// `404 Not Found` response is sent otherwise.
+    ///
+    /// # Examples
+    /// Hiding source maps and a private subdirectory:
+    /// ```
+    /// use actix_files::Files;
+    ///
+    /// let files = Files::new("/", "./static").path_filter(|path, _| {
+    ///     path.extension() != Some("map".as_ref()) && !path.starts_with(".private")
+    /// });
+    /// ```
+    pub fn path_filter<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Path, &RequestHead) -> bool + 'static,
+    {
+        self.path_filter = Some(Rc::new(f));
+        self
+    }
 }
 
 impl HttpServiceFactory for Files {
@@ -312,9 +674,25 @@ impl ServiceFactory<ServiceRequest> for Files {
             default: None,
             renderer: self.renderer.clone(),
             mime_override: self.mime_override.clone(),
+            content_type_override: self.content_type_override.clone(),
             file_flags: self.file_flags,
             guards: self.use_guards.clone(),
             hidden_files: self.hidden_files,
+            path_filter: self.path_filter.clone(),
+            symlinks: self.symlinks,
+            cache_control: self.cache_control.clone(),
+            precompressed: self.precompressed,
+            default_content_type: self.default_content_type.clone(),
+            strip_prefix_segments: self.strip_prefix_segments,
+            not_found_file: self.not_found_file.clone(),
+            response_headers: self.response_headers.clone(),
+            directory_status: self.directory_status,
+            negative_cache: self
+                .negative_cache
+                .map(|(capacity, ttl)| NegativeCache::new(capacity, ttl)),
+            open_file_cache: self
+                .open_file_cache
+                .map(|capacity| OpenFileCache::new(capacity, self.open_file_cache_interval)),
         };
 
         if let Some(ref default) = *self.default.borrow() {
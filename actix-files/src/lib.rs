@@ -14,32 +14,44 @@
 #![deny(rust_2018_idioms)]
 #![warn(missing_docs, missing_debug_implementations)]
 
+use std::path::Path;
+
 use actix_service::boxed::{BoxService, BoxServiceFactory};
 use actix_web::{
-    dev::{ServiceRequest, ServiceResponse},
+    dev::{RequestHead, ServiceRequest, ServiceResponse},
     error::Error,
     http::header::DispositionType,
 };
 use mime_guess::from_ext;
 
+mod cache;
 mod chunked;
+mod digest;
 mod directory;
 mod encoding;
 mod error;
 mod files;
+mod mime_override;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod named;
 mod path_buf;
 mod range;
+mod redirect;
 mod service;
+mod sniff;
 
 pub use crate::chunked::ChunkedReadFile;
+pub use crate::digest::DigestAlgo;
 pub use crate::directory::Directory;
 pub use crate::files::Files;
-pub use crate::named::NamedFile;
-pub use crate::range::HttpRange;
+pub use crate::mime_override::MimeOverride;
+pub use crate::named::{DispositionPolicy, NamedFile, NamedFileOptions};
+pub use crate::range::{HttpRange, HttpRangeError};
+pub use crate::redirect::Redirect;
 pub use crate::service::FilesService;
 
-use self::directory::{directory_listing, DirectoryRenderer};
+use self::directory::{directory_listing, directory_listing_json, DirectoryRenderer};
 use self::error::FilesError;
 use self::path_buf::PathBufWrap;
 
@@ -54,23 +66,33 @@ pub fn file_extension_to_mime(ext: &str) -> mime::Mime {
     from_ext(ext).first_or_octet_stream()
 }
 
-type MimeOverride = dyn Fn(&mime::Name<'_>) -> DispositionType;
+type DispositionOverride = dyn Fn(&mime::Name<'_>) -> DispositionType;
+
+type ContentTypeOverride = dyn Fn(&Path) -> Option<mime::Mime>;
+
+type PathFilter = dyn Fn(&Path, &RequestHead) -> bool;
 
 #[cfg(test)]
 mod tests {
     use std::{
+        cell::RefCell,
+        env,
         fs::{self, File},
+        io,
         ops::Add,
-        time::{Duration, SystemTime},
+        pin::Pin,
+        rc::Rc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
     };
 
-    use actix_service::ServiceFactory;
+    use actix_service::{Service, ServiceFactory};
     use actix_utils::future::ok;
     use actix_web::{
+        dev::{Body, BodySize, MessageBody},
         guard,
         http::{
             header::{self, ContentDisposition, DispositionParam, DispositionType},
-            Method, StatusCode,
+            HeaderValue, Method, StatusCode,
         },
         middleware::Compress,
         test::{self, TestRequest},
@@ -78,6 +100,8 @@ mod tests {
         App, HttpResponse, Responder,
     };
 
+    use futures_util::StreamExt as _;
+
     use super::*;
 
     #[actix_rt::test]
@@ -119,6 +143,42 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
     }
 
+    #[actix_rt::test]
+    async fn test_if_modified_since_whole_seconds() {
+        let path = env::temp_dir().join("actix-files-modified-seconds.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path).unwrap().use_etag(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::ETAG).is_none());
+        let last_modified = resp.headers().get(header::LAST_MODIFIED).unwrap().clone();
+
+        // the modification time usually has a sub-second part that the date sent lacks, which
+        // must not make the file look modified
+        for _ in 0..2 {
+            let req = TestRequest::default()
+                .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+                .to_http_request();
+            let file = NamedFile::open(&path).unwrap().use_etag(false);
+            let resp = file.respond_to(&req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(
+                resp.headers().get(header::LAST_MODIFIED),
+                Some(&last_modified)
+            );
+        }
+
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let since = header::HttpDate::from(modified - Duration::from_secs(1));
+        let req = TestRequest::default()
+            .insert_header((header::IF_MODIFIED_SINCE, since))
+            .to_http_request();
+        let file = NamedFile::open(&path).unwrap().use_etag(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
     #[actix_rt::test]
     async fn test_if_modified_since_with_if_none_match() {
         let file = NamedFile::open("Cargo.toml").unwrap();
@@ -156,6 +216,394 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
     }
 
+    #[actix_rt::test]
+    async fn test_if_unmodified_since_with_if_match() {
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let etag = file.etag().unwrap();
+        let since = header::HttpDate::from(SystemTime::UNIX_EPOCH);
+
+        // `If-Unmodified-Since` is ignored when `If-Match` is present
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, etag.to_string()))
+            .insert_header((header::IF_UNMODIFIED_SINCE, since))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_if_modified_since_with_malformed_if_none_match() {
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let since = header::HttpDate::from(SystemTime::now().add(Duration::from_secs(60)));
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "not an etag"))
+            .insert_header((header::IF_MODIFIED_SINCE, since))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_if_match_without_etag() {
+        let file = NamedFile::open("Cargo.toml").unwrap().use_etag(false);
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"abc\""))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[actix_rt::test]
+    async fn test_if_none_match_any() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        // other methods fail the precondition instead
+        let req = TestRequest::default()
+            .method(Method::POST)
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        // `If-Modified-Since` only applies to `GET` and `HEAD`
+        let since = header::HttpDate::from(SystemTime::now().add(Duration::from_secs(60)));
+        let req = TestRequest::default()
+            .method(Method::POST)
+            .insert_header((header::IF_MODIFIED_SINCE, since))
+            .to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_if_range_etag() {
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let etag = file.etag().unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .insert_header((header::IF_RANGE, etag.to_string()))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 10-20/100"
+        );
+
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .insert_header((header::IF_RANGE, "\"old_etag\""))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_if_range_date() {
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let since = file.last_modified().unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .insert_header((header::IF_RANGE, since))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let since = header::HttpDate::from(SystemTime::UNIX_EPOCH);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .insert_header((header::IF_RANGE, since))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_if_range_validators() {
+        let strong = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .etag()
+            .unwrap();
+        let weak = header::EntityTag::weak(strong.tag().to_owned());
+        let date = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .last_modified()
+            .unwrap()
+            .to_string();
+        let old_date = header::HttpDate::from(SystemTime::UNIX_EPOCH).to_string();
+
+        // (weak etags, `If-Range`, `Range`, expected status)
+        let cases = [
+            (
+                false,
+                strong.to_string(),
+                "bytes=10-20",
+                StatusCode::PARTIAL_CONTENT,
+            ),
+            (
+                false,
+                strong.to_string(),
+                "bytes=0-4,10-14",
+                StatusCode::PARTIAL_CONTENT,
+            ),
+            (false, weak.to_string(), "bytes=10-20", StatusCode::OK),
+            (false, weak.to_string(), "bytes=0-4,10-14", StatusCode::OK),
+            (true, weak.to_string(), "bytes=10-20", StatusCode::OK),
+            (true, strong.to_string(), "bytes=10-20", StatusCode::OK),
+            (
+                false,
+                date.clone(),
+                "bytes=10-20",
+                StatusCode::PARTIAL_CONTENT,
+            ),
+            (
+                true,
+                date.clone(),
+                "bytes=0-4,10-14",
+                StatusCode::PARTIAL_CONTENT,
+            ),
+            (false, old_date.clone(), "bytes=10-20", StatusCode::OK),
+            (false, "garbage".to_owned(), "bytes=10-20", StatusCode::OK),
+        ];
+
+        for (weak_etag, if_range, range, status) in cases.iter() {
+            let file = NamedFile::open("tests/test.binary")
+                .unwrap()
+                .use_weak_etag(*weak_etag);
+            let req = TestRequest::default()
+                .insert_header((header::RANGE, *range))
+                .insert_header((header::IF_RANGE, if_range.as_str()))
+                .to_http_request();
+            let resp = file.respond_to(&req).await.unwrap();
+
+            assert_eq!(resp.status(), *status, "If-Range: {}", if_range);
+            assert_eq!(
+                resp.headers().contains_key(header::CONTENT_RANGE),
+                *status == StatusCode::PARTIAL_CONTENT
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_cache_control() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_cache_control(header::CacheControl(vec![header::CacheDirective::Public]))
+            .set_max_age(Duration::from_secs(60))
+            .set_max_age(Duration::from_secs(3600));
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=3600"
+        );
+
+        // directives are also sent with partial and not modified responses
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_max_age(Duration::from_secs(3600));
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-9"))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=3600"
+        );
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_max_age(Duration::from_secs(3600));
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=3600"
+        );
+
+        // and by the service registered for a file
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_max_age(Duration::from_secs(3600));
+        let srv = test::init_service(App::new().service(file)).await;
+        let req = TestRequest::get().uri("/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=3600"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_expires() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_expires(Duration::from_secs(3600));
+
+        let before = SystemTime::now() + Duration::from_secs(3599);
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let expires = resp.headers().get(header::EXPIRES).unwrap();
+        let expires: SystemTime = expires
+            .to_str()
+            .unwrap()
+            .parse::<header::HttpDate>()
+            .unwrap()
+            .into();
+        assert!(expires >= before);
+        assert!(expires <= SystemTime::now() + Duration::from_secs(3600));
+
+        // durations too long to be added to the current time are clamped
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_expires(Duration::from_secs(u64::MAX));
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().contains_key(header::EXPIRES));
+    }
+
+    #[actix_rt::test]
+    async fn test_modified_before_epoch() {
+        let mut file = NamedFile::open("Cargo.toml").unwrap();
+        file.modified = Some(SystemTime::UNIX_EPOCH - Duration::from_secs(60 * 60 * 24));
+        assert!(file.etag().is_none());
+
+        let since = header::HttpDate::from(SystemTime::now());
+        let req = TestRequest::default()
+            .insert_header((header::IF_MODIFIED_SINCE, since))
+            .insert_header((header::IF_UNMODIFIED_SINCE, since))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::ETAG).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_etag_without_inode() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .use_etag_without_inode(true);
+        let etag = file.etag().unwrap();
+        assert_eq!(etag.tag().split(':').count(), 3);
+
+        let mtime = file.modified.unwrap().duration_since(UNIX_EPOCH).unwrap();
+        assert!(etag
+            .tag()
+            .starts_with(&format!("{:x}:{:x}:", file.md.len(), mtime.as_secs())));
+
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        assert_eq!(file.etag().unwrap().tag().split(':').count(), 4);
+    }
+
+    #[actix_rt::test]
+    async fn test_etag_fn() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_etag_fn(|md, path| {
+                Some(header::EntityTag::strong(format!(
+                    "{}-{}",
+                    path.display(),
+                    md.len()
+                )))
+            });
+        let etag = format!("\"Cargo.toml-{}\"", file.md.len());
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), etag.as_str());
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_etag_fn(|_, _| None);
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::ETAG).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_weak_etag() {
+        let file = NamedFile::open("Cargo.toml").unwrap().use_weak_etag(true);
+        let etag = file.etag().unwrap();
+        assert!(etag.weak);
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("W/"));
+
+        // weak comparison is used for If-None-Match
+        let file = NamedFile::open("Cargo.toml").unwrap().use_weak_etag(true);
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.to_string()))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        // strong comparison is used for If-Match
+        let file = NamedFile::open("Cargo.toml").unwrap().use_weak_etag(true);
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, etag.to_string()))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[actix_rt::test]
+    async fn test_set_modified() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        assert_eq!(file.modified(), file.metadata().modified().ok());
+        assert_eq!(
+            file.metadata().len(),
+            std::fs::metadata("Cargo.toml").unwrap().len()
+        );
+
+        let file = file.set_modified(modified);
+        assert_eq!(file.modified(), Some(modified));
+        assert!(file
+            .etag()
+            .unwrap()
+            .tag()
+            .contains(&format!(":{:x}:", 1_000_000_000)));
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::LAST_MODIFIED).unwrap(),
+            "Sun, 09 Sep 2001 01:46:40 GMT"
+        );
+    }
+
     #[actix_rt::test]
     async fn test_named_file_text() {
         assert!(NamedFile::open("test--").is_err());
@@ -180,6 +628,35 @@ mod tests {
         );
     }
 
+    #[actix_rt::test]
+    async fn test_named_file_from_file_with_metadata() {
+        let file = File::open("tests/test.binary").unwrap();
+        let md = fs::metadata("tests/test.binary").unwrap();
+        let file = NamedFile::from_file_with_metadata(file, "tests/test.binary", md).unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body().size(), BodySize::Sized(100));
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_open_async() {
+        assert!(NamedFile::open_async("test--").await.is_err());
+
+        let file = NamedFile::open_async("Cargo.toml").await.unwrap();
+        assert_eq!(file.md.len(), fs::metadata("Cargo.toml").unwrap().len());
+        assert!(file.last_modified().is_some());
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/x-toml"
+        );
+    }
+
     #[actix_rt::test]
     async fn test_named_file_content_disposition() {
         assert!(NamedFile::open("test--").is_err());
@@ -227,7 +704,41 @@ mod tests {
         );
         assert_eq!(
             resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
-            "inline; filename=\"貨物.toml\"; filename*=UTF-8''%E8%B2%A8%E7%89%A9.toml"
+            "inline; filename=\"__.toml\"; filename*=UTF-8''%E8%B2%A8%E7%89%A9.toml"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_open_with() {
+        let options = NamedFileOptions::new()
+            .use_etag(false)
+            .use_last_modified(false)
+            .prefer_utf8(true)
+            .set_disposition_policy(DispositionPolicy::Never)
+            .set_cache_control(header::CacheControl(vec![header::CacheDirective::NoCache]));
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open_with("tests/utf8.txt", &options).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
+        );
+        assert!(resp.headers().get(header::CONTENT_DISPOSITION).is_none());
+        assert!(resp.headers().get(header::ETAG).is_none());
+        assert!(resp.headers().get(header::LAST_MODIFIED).is_none());
+
+        // defaults match `open`
+        let file = NamedFile::open_with("tests/utf8.txt", &NamedFileOptions::new()).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::ETAG).is_some());
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
         );
     }
 
@@ -236,6 +747,11 @@ mod tests {
         let mut file = NamedFile::open("Cargo.toml")
             .unwrap()
             .set_content_type(mime::TEXT_XML);
+        assert_eq!(file.content_type(), &mime::TEXT_XML);
+        assert_eq!(
+            file.content_disposition().disposition,
+            header::DispositionType::Inline
+        );
         {
             file.file();
             let _f: &File = &file;
@@ -256,6 +772,73 @@ mod tests {
         );
     }
 
+    #[actix_rt::test]
+    async fn test_named_file_charset() {
+        let req = TestRequest::default().to_http_request();
+
+        let file = NamedFile::open("tests/utf8.txt").unwrap().prefer_utf8(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+
+        let file = NamedFile::open("tests/utf8.txt")
+            .unwrap()
+            .set_content_type_charset(header::Charset::Iso_8859_1);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=iso-8859-1"
+        );
+
+        let file = NamedFile::open("tests/test.png")
+            .unwrap()
+            .set_content_type_charset(header::Charset::Iso_8859_1);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_mime_override() {
+        let overrides = MimeOverride::new()
+            .add("binary", mime::IMAGE_PNG)
+            .add("toml", mime::APPLICATION_OCTET_STREAM);
+        let req = TestRequest::default().to_http_request();
+
+        let file = NamedFile::open_with_mime_override("tests/test.binary", &overrides).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"test.binary\""
+        );
+
+        let file = NamedFile::open_with_mime_override("Cargo.toml", &overrides).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"Cargo.toml\""
+        );
+
+        let file = NamedFile::open_with_mime_override("tests/test.png", &overrides).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+    }
+
     #[actix_rt::test]
     async fn test_named_file_image() {
         let mut file = NamedFile::open("tests/test.png").unwrap();
@@ -308,6 +891,184 @@ mod tests {
         );
     }
 
+    #[actix_rt::test]
+    async fn test_named_file_filename_sanitized() {
+        let file = File::open("Cargo.toml").unwrap();
+        let file = NamedFile::from_file(file, "evil\r\nX-Injected: \"1\".txt").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        let cd = resp
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert_eq!(
+            cd,
+            "inline; filename=\"evil__X-Injected: _1_.txt\"; \
+             filename*=UTF-8''evil%0D%0AX%2DInjected%3A%20%221%22.txt"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_set_inline_attachment() {
+        let req = TestRequest::default().to_http_request();
+
+        let file = NamedFile::open("tests/test.binary").unwrap().set_inline();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"test.binary\""
+        );
+
+        let file = NamedFile::open("tests/test.png")
+            .unwrap()
+            .disable_content_disposition()
+            .set_attachment(None);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"test.png\""
+        );
+
+        let file = NamedFile::open("tests/test.png")
+            .unwrap()
+            .set_attachment(Some("写真.png".to_owned()));
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"__.png\"; filename*=UTF-8''%E5%86%99%E7%9C%9F.png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_set_download_filename() {
+        let req = TestRequest::default().to_http_request();
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .disable_content_disposition()
+            .set_download_filename("report-2024.pdf");
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"report-2024.pdf\""
+        );
+
+        let file = NamedFile::open("tests/test.png")
+            .unwrap()
+            .set_download_filename("写真.png");
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"__.png\"; filename*=UTF-8''%E5%86%99%E7%9C%9F.png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_detect_content_type() {
+        let path = env::temp_dir().join("actix-files-upload.bin");
+        fs::copy("tests/test.png", &path).unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .detect_content_type_from_content(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"actix-files-upload.bin\""
+        );
+
+        // the sniffed bytes are still sent
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read("tests/test.png").unwrap());
+
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .detect_content_type_from_content(false);
+        assert_eq!(file.content_type(), &mime::APPLICATION_OCTET_STREAM);
+
+        // unrecognized content keeps the type guessed from the extension
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .detect_content_type_from_content(true);
+        assert_eq!(file.content_type().to_string(), "text/x-toml");
+
+        // as do formats recognized with a related or more generic type
+        let docx = env::temp_dir().join("actix-files-report.docx");
+        fs::write(
+            &docx,
+            b"PK\x03\x04\x14\0\x06\0\x08\0\0\0!\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x13\0\0\0[Content_Types].xml",
+        )
+        .unwrap();
+        let file = NamedFile::open(&docx)
+            .unwrap()
+            .detect_content_type_from_content(true);
+        assert_eq!(
+            file.content_type().to_string(),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+
+        let avif = env::temp_dir().join("actix-files-photo.avif");
+        fs::write(&avif, b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf").unwrap();
+        let file = NamedFile::open(&avif)
+            .unwrap()
+            .detect_content_type_from_content(true);
+        assert_eq!(file.content_type().to_string(), "image/avif");
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_set_content_type_and_disposition() {
+        let req = TestRequest::default().to_http_request();
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .set_content_type_and_disposition(mime::TEXT_PLAIN);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"test.binary\""
+        );
+
+        // an explicitly set disposition is kept
+        let file = NamedFile::open("tests/test.png")
+            .unwrap()
+            .set_attachment(None)
+            .set_content_type_and_disposition(mime::IMAGE_JPEG);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"test.png\""
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_disposition_policy() {
+        let req = TestRequest::default().to_http_request();
+        let disposition = |path, policy| {
+            let file = NamedFile::open(path)
+                .unwrap()
+                .set_disposition_policy(policy);
+            let resp = file.into_response(&req);
+            resp.headers().get(header::CONTENT_DISPOSITION).cloned()
+        };
+
+        assert!(disposition("tests/test.png", DispositionPolicy::Always).is_some());
+        assert!(disposition("tests/test.png", DispositionPolicy::NeverForInline).is_none());
+        assert!(disposition("tests/test.binary", DispositionPolicy::NeverForInline).is_some());
+        assert!(disposition("tests/test.binary", DispositionPolicy::Never).is_none());
+    }
+
     #[actix_rt::test]
     async fn test_named_file_binary() {
         let mut file = NamedFile::open("tests/test.binary").unwrap();
@@ -355,6 +1116,59 @@ mod tests {
             "inline; filename=\"Cargo.toml\""
         );
         assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.body().size(),
+            BodySize::Sized(fs::metadata("Cargo.toml").unwrap().len())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_status_code_conditional() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_status_code(StatusCode::NOT_FOUND);
+        let etag = file.etag().unwrap();
+
+        let req = TestRequest::default()
+            .insert_header(header::IfNoneMatch::Items(vec![etag.clone()]))
+            .to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            resp.headers().get(header::ETAG).unwrap(),
+            etag.to_string().as_str()
+        );
+
+        let req = TestRequest::default()
+            .insert_header(header::IfMatch::Items(vec![header::EntityTag::strong(
+                "other".to_owned(),
+            )]))
+            .to_http_request();
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_status_code(StatusCode::NOT_FOUND);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        // opted out
+        let req = TestRequest::default()
+            .insert_header(header::IfNoneMatch::Items(vec![etag]))
+            .to_http_request();
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_status_code(StatusCode::NOT_FOUND)
+            .conditional_custom_status(false)
+            .set_max_age(Duration::from_secs(60));
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(resp.headers().get(header::ETAG).is_none());
+
+        // caching headers are still sent
+        assert!(resp.headers().contains_key(header::DATE));
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=60"
+        );
     }
 
     #[actix_rt::test]
@@ -412,54 +1226,434 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn test_named_file_content_range_headers() {
-        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+    async fn test_named_file_success_status_code() {
+        let file = || {
+            NamedFile::open("tests/test.binary")
+                .unwrap()
+                .set_status_code(StatusCode::NON_AUTHORITATIVE_INFORMATION)
+        };
 
-        // Valid range header
-        let response = srv
-            .get("/tests/test.binary")
-            .insert_header((header::RANGE, "bytes=10-20"))
-            .send()
-            .await
-            .unwrap();
-        let content_range = response.headers().get(header::CONTENT_RANGE).unwrap();
-        assert_eq!(content_range.to_str().unwrap(), "bytes 10-20/100");
+        let req = TestRequest::default().to_http_request();
+        let resp = file().respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NON_AUTHORITATIVE_INFORMATION);
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+        assert!(resp.headers().get(header::LAST_MODIFIED).is_some());
 
-        // Invalid range header
-        let response = srv
-            .get("/tests/test.binary")
-            .insert_header((header::RANGE, "bytes=10-5"))
-            .send()
-            .await
-            .unwrap();
-        let content_range = response.headers().get(header::CONTENT_RANGE).unwrap();
-        assert_eq!(content_range.to_str().unwrap(), "bytes */100");
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-4"))
+            .to_http_request();
+        let resp = file().respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_http_request();
+        let resp = file().respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
     }
 
     #[actix_rt::test]
-    async fn test_named_file_content_length_headers() {
-        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+    async fn test_named_file_set_content_length() {
+        let data = fs::read("tests/test.binary").unwrap();
 
-        // Valid range header
-        let response = srv
-            .get("/tests/test.binary")
-            .insert_header((header::RANGE, "bytes=10-20"))
-            .send()
-            .await
-            .unwrap();
-        let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
-        assert_eq!(content_length.to_str().unwrap(), "11");
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .set_content_length(10);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.body().size(), BodySize::Sized(10));
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, &data[..10]);
 
-        // Valid range header, starting from 0
-        let response = srv
-            .get("/tests/test.binary")
-            .insert_header((header::RANGE, "bytes=0-20"))
-            .send()
-            .await
-            .unwrap();
+        // ranges are resolved against the set length
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=-4"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .set_content_length(10);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 6-9/10"
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, &data[6..10]);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_into_response_for_range() {
+        let data = fs::read("tests/test.binary").unwrap();
+        let len = data.len() as u64;
+
+        // the requested range is replaced
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-0"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.into_response_for_range(&req, 10, 5);
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            format!("bytes 10-14/{}", len).as_str()
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, &data[10..15]);
+
+        let req = TestRequest::default().to_http_request();
+        for &(start, length) in &[(len - 1, 2), (0, 0), (u64::MAX, 1)] {
+            let file = NamedFile::open("tests/test.binary").unwrap();
+            let resp = file.into_response_for_range(&req, start, length);
+            assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_range_units() {
+        let data = fs::read("tests/test.binary").unwrap();
+
+        // ranges in other units are ignored
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "items=0-9"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=9999999-"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            format!("bytes */{}", data.len()).as_str()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_suffix_range() {
+        let data = fs::read("tests/test.binary").unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=-30"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 70-99/100"
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, &data[70..]);
+
+        // longer than the file is clamped to the whole file
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=-500"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 0-99/100"
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=-0"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */100"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_max_ranges() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-1,3-4,6-7"))
+            .to_http_request();
+
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+
+        let file = NamedFile::open("tests/test.binary").unwrap().max_ranges(2);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */100"
+        );
+
+        // ranges are counted once merged
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-1,2-4,3-7"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap().max_ranges(1);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 0-7/100"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_coalesce_ranges() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=20-29,5-9,10-24"))
+            .to_http_request();
+
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 5-29/100"
+        );
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .coalesce_ranges(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 20-29/100"
+        );
+
+        // of disjoint ranges, the first requested one is served
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=50-59,0-9"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 50-59/100"
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read("tests/test.binary").unwrap()[50..60]);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_ignore_unsatisfiable_range() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=200-300"))
+            .to_http_request();
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .ignore_unsatisfiable_range(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read("tests/test.binary").unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_disable_ranges() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .to_http_request();
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .accept_ranges(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::ACCEPT_RANGES).is_none());
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+        assert_eq!(resp.body().size(), BodySize::Sized(100));
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_advertise_ranges() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .to_http_request();
+
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .advertise_ranges(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(resp.headers().get(header::ACCEPT_RANGES).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_empty_ranges() {
+        let path = env::temp_dir().join("actix-files-empty-ranges.txt");
+        fs::write(&path, "").unwrap();
+
+        for range in &["bytes=0-", "bytes=0-0", "bytes=-1"] {
+            let req = TestRequest::default()
+                .insert_header((header::RANGE, *range))
+                .to_http_request();
+            let file = NamedFile::open(&path).unwrap();
+            let resp = file.respond_to(&req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(
+                resp.headers().get(header::CONTENT_RANGE).unwrap(),
+                "bytes */0"
+            );
+        }
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body().size(), BodySize::Sized(0));
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_empty_as_no_content() {
+        let path = env::temp_dir().join("actix-files-empty-no-content.txt");
+        fs::write(&path, "").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path).unwrap().empty_as_no_content(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.body().size(), BodySize::Empty);
+        assert!(resp.headers().get(header::CONTENT_LENGTH).is_none());
+
+        // other status codes are kept
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .empty_as_no_content(true)
+            .set_status_code(StatusCode::CREATED);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .empty_as_no_content(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_open_ended_range() {
+        let path = env::temp_dir().join("actix-files-open-ended-range.binary");
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        for &(range, start) in &[("bytes=0-", 0), ("bytes=500-", 500), ("bytes=999-", 999)] {
+            let req = TestRequest::default()
+                .insert_header((header::RANGE, range))
+                .to_http_request();
+            let file = NamedFile::open(&path).unwrap();
+            let resp = file.respond_to(&req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                resp.headers().get(header::CONTENT_RANGE).unwrap(),
+                format!("bytes {}-999/1000", start).as_str()
+            );
+            assert_eq!(resp.body().size(), BodySize::Sized(1000 - start as u64));
+            let bytes = test::load_body(resp.into_body()).await.unwrap();
+            assert_eq!(bytes, data[start..]);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_unsatisfiable_range_length() {
+        let path = env::temp_dir().join("actix-files-unsatisfiable-range.binary");
+        fs::write(&path, vec![0; 1000]).unwrap();
+
+        for range in &["bytes=abc", "bytes=1000-", "bytes=500-400"] {
+            let req = TestRequest::default()
+                .insert_header((header::RANGE, *range))
+                .to_http_request();
+            let file = NamedFile::open(&path).unwrap();
+            let resp = file.respond_to(&req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(
+                resp.headers().get(header::CONTENT_RANGE).unwrap(),
+                "bytes */1000"
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_range_headers() {
+        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+
+        // Valid range header
+        let response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .send()
+            .await
+            .unwrap();
+        let content_range = response.headers().get(header::CONTENT_RANGE).unwrap();
+        assert_eq!(content_range.to_str().unwrap(), "bytes 10-20/100");
+
+        // Invalid range header
+        let response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=10-5"))
+            .send()
+            .await
+            .unwrap();
+        let content_range = response.headers().get(header::CONTENT_RANGE).unwrap();
+        assert_eq!(content_range.to_str().unwrap(), "bytes */100");
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_length_headers() {
+        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+
+        // Valid range header
+        let response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .send()
+            .await
+            .unwrap();
+        let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
+        assert_eq!(content_length.to_str().unwrap(), "11");
+
+        // Valid range header, starting from 0
+        let response = srv
+            .get("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=0-20"))
+            .send()
+            .await
+            .unwrap();
         let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
         assert_eq!(content_length.to_str().unwrap(), "21");
 
+        // Valid range header on a HEAD request
+        let response = srv
+            .head("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=0-99"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
+        assert_eq!(content_length.to_str().unwrap(), "100");
+
+        let response = srv
+            .head("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=50-"))
+            .send()
+            .await
+            .unwrap();
+        let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
+        assert_eq!(content_length.to_str().unwrap(), "50");
+
         // Without range header
         let mut response = srv.get("/tests/test.binary").send().await.unwrap();
         let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
@@ -475,371 +1669,1907 @@ mod tests {
         assert_eq!(bytes, data);
     }
 
-    #[actix_rt::test]
-    async fn test_head_content_length_headers() {
-        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+    #[actix_rt::test]
+    async fn test_named_file_bytes_sent() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let sent2 = Rc::clone(&sent);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-49"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(20)
+            .on_bytes_sent(move |n| sent2.borrow_mut().push(n));
+        let resp = file.respond_to(&req).await.unwrap();
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes.len(), 50);
+        assert_eq!(*sent.borrow(), vec![20, 40, 50, 50]);
+
+        // aborted download reports what was sent before the body was dropped
+        sent.borrow_mut().clear();
+        let sent2 = Rc::clone(&sent);
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(20)
+            .on_bytes_sent(move |n| sent2.borrow_mut().push(n));
+        let resp = file.respond_to(&req).await.unwrap();
+        let mut body = resp.into_body();
+        futures_util::future::poll_fn(|cx| Pin::new(&mut body).poll_next(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        drop(body);
+        assert_eq!(*sent.borrow(), vec![20, 20]);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_abort_on_change() {
+        let path = env::temp_dir().join("actix-files-abort-on-change.txt");
+        let req = TestRequest::default().to_http_request();
+
+        async fn next_chunk<B: MessageBody + Unpin>(
+            body: &mut B,
+        ) -> Option<Result<Bytes, B::Error>> {
+            futures_util::future::poll_fn(|cx| Pin::new(&mut *body).poll_next(cx)).await
+        }
+
+        // truncated in place
+        fs::write(&path, vec![b'a'; 100]).unwrap();
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .with_buffer_size(10)
+            .abort_on_change(true)
+            .change_check_interval(Duration::from_secs(0));
+        let mut body = file.respond_to(&req).await.unwrap().into_body();
+        assert!(next_chunk(&mut body).await.unwrap().is_ok());
+        fs::write(&path, "short").unwrap();
+        assert!(next_chunk(&mut body).await.unwrap().is_err());
+
+        // replaced by renaming another file over it
+        #[cfg(unix)]
+        {
+            let tmp_path = env::temp_dir().join("actix-files-abort-on-change.txt.tmp");
+            fs::write(&path, vec![b'a'; 100]).unwrap();
+            let file = NamedFile::open(&path)
+                .unwrap()
+                .with_buffer_size(10)
+                .abort_on_change(true)
+                .change_check_interval(Duration::from_secs(0));
+            let mut body = file.respond_to(&req).await.unwrap().into_body();
+            assert!(next_chunk(&mut body).await.unwrap().is_ok());
+            fs::write(&tmp_path, vec![b'b'; 100]).unwrap();
+            fs::rename(&tmp_path, &path).unwrap();
+            assert!(next_chunk(&mut body).await.unwrap().is_err());
+        }
+
+        // not checked by default
+        fs::write(&path, vec![b'a'; 100]).unwrap();
+        let file = NamedFile::open(&path).unwrap().with_buffer_size(10);
+        let mut body = file.respond_to(&req).await.unwrap().into_body();
+        assert!(next_chunk(&mut body).await.unwrap().is_ok());
+        fs::write(&path, vec![b'a'; 50]).unwrap();
+        assert!(next_chunk(&mut body).await.unwrap().is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_read_ahead() {
+        let data = fs::read("tests/test.binary").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(7)
+            .read_ahead(4);
+        let resp = file.respond_to(&req).await.unwrap();
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-49"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(3)
+            .read_ahead(8);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data[10..50]);
+    }
+
+    #[actix_rt::test]
+    async fn test_chunked_read_file_shared_handle() {
+        let data = fs::read("tests/test.binary").unwrap();
+        let file = File::open("tests/test.binary").unwrap();
+
+        let mut first = ChunkedReadFile::new(10, 0, file.try_clone().unwrap()).buffer_size(5);
+        let mut second = ChunkedReadFile::new(10, 50, file).buffer_size(5);
+
+        // interleaved reads through handles sharing a file cursor each see their own range
+        let mut got = Vec::new();
+        for _ in 0..2 {
+            got.push(first.next().await.unwrap().unwrap());
+            got.push(second.next().await.unwrap().unwrap());
+        }
+
+        assert_eq!(got[0], &data[0..5]);
+        assert_eq!(got[1], &data[50..55]);
+        assert_eq!(got[2], &data[5..10]);
+        assert_eq!(got[3], &data[55..60]);
+        assert!(first.next().await.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_chunked_read_file_from_reader() {
+        let data = fs::read("tests/test.binary").unwrap();
+
+        let mut stream =
+            ChunkedReadFile::from_reader(25, 10, io::Cursor::new(data.clone())).buffer_size(10);
+        let mut got = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            got.push(chunk.unwrap());
+        }
+
+        assert_eq!(
+            got.iter().map(Bytes::len).collect::<Vec<_>>(),
+            vec![10, 10, 5]
+        );
+        assert_eq!(got.concat(), &data[10..35]);
+
+        // a reader ending early fails the stream
+        let mut stream = ChunkedReadFile::from_reader(10, 95, io::Cursor::new(data.clone()));
+        assert_eq!(stream.next().await.unwrap().unwrap(), &data[95..]);
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[actix_rt::test]
+    async fn test_named_file_mmap() {
+        let data = fs::read("tests/test.binary").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .use_mmap(true)
+            .with_buffer_size(30);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.body().size(), BodySize::Sized(100));
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=7-56"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary").unwrap().use_mmap(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, &data[7..57]);
+
+        // empty regions cannot be mapped and are read as usual
+        let path = env::temp_dir().join("actix-files-mmap-empty.binary");
+        fs::write(&path, "").unwrap();
+        let file = NamedFile::open(&path).unwrap().use_mmap(true);
+        let req = TestRequest::default().to_http_request();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(test::load_body(resp.into_body()).await.unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_buffer_size() {
+        let srv = test::init_service(App::new().service(web::resource("/").to(|| async {
+            NamedFile::open("tests/test.binary")
+                .unwrap()
+                .with_buffer_size(7)
+        })))
+        .await;
+
+        let req = TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = test::read_body(res).await;
+        let data = web::Bytes::from(fs::read("tests/test.binary").unwrap());
+        assert_eq!(bytes, data);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer size must be greater than 0")]
+    fn test_named_file_zero_buffer_size() {
+        let _ = NamedFile::open("Cargo.toml").unwrap().with_buffer_size(0);
+    }
+
+    #[actix_rt::test]
+    async fn test_head_content_length_headers() {
+        let srv = actix_test::start(|| App::new().service(Files::new("/", ".")));
+
+        let response = srv.head("/tests/test.binary").send().await.unwrap();
+
+        let content_length = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .unwrap()
+            .to_str()
+            .unwrap();
+
+        assert_eq!(content_length, "100");
+    }
+
+    #[actix_rt::test]
+    async fn test_head_empty_body() {
+        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+
+        let req = TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/tests/test.binary")
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.response().body().size(), BodySize::Sized(100));
+        assert!(res.headers().contains_key(header::ETAG));
+        assert_eq!(res.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+
+        let bytes = test::read_body(res).await;
+        assert!(bytes.is_empty());
+
+        // the length sent is that of the range
+        let req = TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/tests/test.binary")
+            .insert_header((header::RANGE, "bytes=10-49"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.response().body().size(), BodySize::Sized(40));
+        assert_eq!(
+            res.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 10-49/100"
+        );
+
+        let bytes = test::read_body(res).await;
+        assert!(bytes.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_static_files_with_spaces() {
+        let srv = test::init_service(
+            App::new().service(Files::new("/", ".").index_file("Cargo.toml")),
+        )
+        .await;
+        let request = TestRequest::get()
+            .uri("/tests/test%20space.binary")
+            .to_request();
+        let response = test::call_service(&srv, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = test::read_body(response).await;
+        let data = web::Bytes::from(fs::read("tests/test space.binary").unwrap());
+        assert_eq!(bytes, data);
+    }
+
+    #[actix_rt::test]
+    async fn test_files_not_allowed() {
+        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+
+        let req = TestRequest::default()
+            .uri("/Cargo.toml")
+            .method(Method::POST)
+            .to_request();
+
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+        let req = TestRequest::default()
+            .method(Method::PUT)
+            .uri("/Cargo.toml")
+            .to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            resp.headers().get(header::ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS"
+        );
+
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/Cargo.toml")
+            .to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            resp.headers().get(header::ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_files_guards() {
+        let srv = test::init_service(
+            App::new().service(Files::new("/", ".").method_guard(guard::Post())),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .uri("/Cargo.toml")
+            .method(Method::POST)
+            .to_request();
+
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_files_routing_guard() {
+        let srv = test::init_service(
+            App::new()
+                .service(Files::new("/", ".").guard(guard::Header("X-Internal", "1")))
+                .default_service(web::to(|| HttpResponse::Ok().body("public"))),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/Cargo.toml")
+            .insert_header(("X-Internal", "1"))
+            .to_request();
+        let bytes = test::read_body(test::call_service(&srv, req).await).await;
+        assert_eq!(bytes, Bytes::from(fs::read("Cargo.toml").unwrap()));
+
+        // routing continues with the next service when the guard fails
+        let req = TestRequest::get().uri("/Cargo.toml").to_request();
+        let bytes = test::read_body(test::call_service(&srv, req).await).await;
+        assert_eq!(bytes, Bytes::from_static(b"public"));
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_encoding() {
+        let srv = test::init_service(App::new().wrap(Compress::default()).service(
+            web::resource("/").to(|| async {
+                NamedFile::open("Cargo.toml")
+                    .unwrap()
+                    .set_content_encoding(header::ContentEncoding::Identity)
+            }),
+        ))
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let res = test::call_service(&srv, request).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_encoding_gzip() {
+        let srv = test::init_service(App::new().wrap(Compress::default()).service(
+            web::resource("/").to(|| async {
+                NamedFile::open("Cargo.toml")
+                    .unwrap()
+                    .set_content_encoding(header::ContentEncoding::Gzip)
+            }),
+        ))
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let res = test::call_service(&srv, request).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get(header::CONTENT_ENCODING)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "gzip"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_files_precompressed_with_compress() {
+        let srv = test::init_service(
+            App::new()
+                .wrap(Compress::default())
+                .service(Files::new("/", "tests/fixtures").prefer_precompressed()),
+        )
+        .await;
+
+        // the variant is sent as is rather than compressed again
+        let req = TestRequest::get()
+            .uri("/precompressed/app.js")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let encodings: Vec<_> = res.headers().get_all(header::CONTENT_ENCODING).collect();
+        assert_eq!(encodings, vec!["gzip"]);
+        let bytes = test::read_body(res).await;
+        assert_eq!(
+            bytes,
+            fs::read("tests/fixtures/precompressed/app.js.gz").unwrap()
+        );
+
+        // without a variant, the middleware compresses the file itself
+        let req = TestRequest::get()
+            .uri("/precompressed/style.css")
+            .insert_header((header::ACCEPT_ENCODING, "deflate"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(
+            res.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "deflate"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_encoding_vary() {
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::VARY).is_none());
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_content_encoding(header::ContentEncoding::Gzip);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_date_header() {
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        let date = resp.headers().get(header::DATE).unwrap().to_str().unwrap();
+        assert!(date.parse::<header::HttpDate>().is_ok());
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .use_date_header(false);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::DATE).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_content_language() {
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_content_encoding(header::ContentEncoding::Gzip)
+            .set_content_language(header::LanguageTag::parse("en-GB").unwrap());
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_LANGUAGE).unwrap(),
+            "en-GB"
+        );
+        let vary: Vec<_> = resp.headers().get_all(header::VARY).collect();
+        assert_eq!(vary, vec!["Accept-Encoding", "Accept-Language"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_precompressed() {
+        let path = "tests/fixtures/precompressed/app.js";
+
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip, br"))
+            .to_http_request();
+        let file = NamedFile::open_with_precompressed(path, &req).unwrap();
+        let etag = file.etag().unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/javascript"
+        );
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(
+            resp.headers().get(header::ETAG).unwrap(),
+            etag.to_string().as_str()
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read(format!("{}.br", path)).unwrap());
+
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_http_request();
+        let file = NamedFile::open_with_precompressed(path, &req).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read(format!("{}.gz", path)).unwrap());
+
+        // no variant is acceptable
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "deflate"))
+            .to_http_request();
+        let file = NamedFile::open_with_precompressed(path, &req).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read(path).unwrap());
+
+        // acceptable variant does not exist
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_http_request();
+        let file = NamedFile::open_with_precompressed("Cargo.toml", &req).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_encoded_ranges() {
+        let path = "tests/fixtures/precompressed/app.js";
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .insert_header((header::RANGE, "bytes=0-4"))
+            .to_http_request();
+
+        let file = NamedFile::open_with_precompressed(path, &req).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "none");
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read(format!("{}.gz", path)).unwrap());
+
+        // an encoding for the compress middleware is not applied to ranges
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_content_encoding(header::ContentEncoding::Gzip);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read("Cargo.toml").unwrap()[..5]);
+
+        let file = NamedFile::open_with_precompressed(path, &req)
+            .unwrap()
+            .encoded_ranges(true);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, fs::read(format!("{}.gz", path)).unwrap()[..5]);
+
+        // the plain file is served when no variant is accepted
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-4"))
+            .to_http_request();
+        let file = NamedFile::open_with_precompressed(path, &req).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    }
+
+    #[actix_rt::test]
+    async fn test_files_prefer_precompressed() {
+        let st = Files::new("/", "tests/fixtures")
+            .prefer_precompressed()
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::with_uri("/precompressed/app.js")
+            .insert_header((header::ACCEPT_ENCODING, "gzip, zstd"))
+            .to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "zstd"
+        );
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        let bytes = test::read_body(resp).await;
+        assert_eq!(
+            bytes,
+            fs::read("tests/fixtures/precompressed/app.js.zst").unwrap()
+        );
+
+        // the content type is guessed from the requested file rather than the variant
+        let req = TestRequest::with_uri("/precompressed/style.css")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/css"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        let bytes = test::read_body(resp).await;
+        assert_eq!(
+            bytes,
+            fs::read("tests/fixtures/precompressed/style.css.gz").unwrap()
+        );
+
+        let req = TestRequest::with_uri("/precompressed/app.js").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+        let bytes = test::read_body(resp).await;
+        assert_eq!(
+            bytes,
+            fs::read("tests/fixtures/precompressed/app.js").unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_allowed_method() {
+        let req = TestRequest::default().method(Method::GET).to_http_request();
+        let file = NamedFile::open("Cargo.toml").unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_static_files() {
+        let srv =
+            test::init_service(App::new().service(Files::new("/", ".").show_files_listing()))
+                .await;
+        let req = TestRequest::with_uri("/missing").to_request();
+
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+
+        let req = TestRequest::default().to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let srv =
+            test::init_service(App::new().service(Files::new("/", ".").show_files_listing()))
+                .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+
+        let bytes = test::read_body(resp).await;
+        assert!(format!("{:?}", bytes).contains("/tests/test.png"));
+    }
+
+    #[actix_rt::test]
+    async fn test_static_files_listing_json() {
+        let srv = test::init_service(
+            App::new().service(Files::new("/", ".").show_files_listing_json()),
+        )
+        .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        let bytes = test::read_body(resp).await;
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&bytes).unwrap();
+        let entry = |name: &str| {
+            entries
+                .iter()
+                .find(|entry| entry["name"] == name)
+                .unwrap_or_else(|| panic!("no entry for {}", name))
+        };
+
+        let png = entry("test.png");
+        assert_eq!(png["size"], fs::metadata("tests/test.png").unwrap().len());
+        assert_eq!(png["is_dir"], false);
+        assert!(png["modified"].is_u64());
+        assert_eq!(entry("fixtures")["is_dir"], true);
+    }
+
+    #[actix_rt::test]
+    async fn test_static_files_listing_renderer() {
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", ".")
+                    .show_files_listing()
+                    .files_listing_renderer(|dir, req| {
+                        Ok(ServiceResponse::new(
+                            req.clone(),
+                            HttpResponse::Ok().body(format!(
+                                "listing of {}",
+                                dir.path.file_name().unwrap().to_string_lossy()
+                            )),
+                        ))
+                    }),
+            ),
+        )
+        .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let bytes = test::read_body(resp).await;
+        assert_eq!(bytes, web::Bytes::from("listing of tests"));
+    }
+
+    #[actix_rt::test]
+    async fn test_redirect() {
+        let srv = test::init_service(
+            App::new()
+                .service(Redirect::new("/assets", "/static"))
+                .service(
+                    Redirect::new("/old.toml", "/static/Cargo.toml")
+                        .status_code(StatusCode::MOVED_PERMANENTLY),
+                )
+                .service(Files::new("/static", ".")),
+        )
+        .await;
+
+        let req = TestRequest::with_uri("/assets/tests/test.png?v=2").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "/static/tests/test.png?v=2"
+        );
+
+        let req = TestRequest::with_uri("/old.toml").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "/static/Cargo.toml"
+        );
+
+        // only whole path segments are matched
+        let req = TestRequest::with_uri("/assetsfoo").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the location stays on this host
+        let srv = test::init_service(App::new().service(Redirect::new("/old", "/"))).await;
+        let req = TestRequest::with_uri("/old//evil.com").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/evil.com");
+    }
+
+    #[actix_rt::test]
+    async fn test_redirect_to_slash_directory() {
+        // should not redirect if no index and files listing is disabled
+        let srv = test::init_service(
+            App::new().service(Files::new("/", ".").redirect_to_slash_directory()),
+        )
+        .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // should redirect if index present
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", ".")
+                    .index_file("test.png")
+                    .redirect_to_slash_directory(),
+            ),
+        )
+        .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/tests/");
+
+        // should keep the query string
+        let req = TestRequest::with_uri("/tests?page=2").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "/tests/?page=2"
+        );
+
+        // leading slashes must not turn the location into one on another host
+        for (uri, location) in &[
+            ("//tests", "/tests/"),
+            ("///tests?page=2", "/tests/?page=2"),
+        ] {
+            let req = TestRequest::with_uri(uri).to_request();
+            let resp = test::call_service(&srv, req).await;
+            assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT, "{}", uri);
+            assert_eq!(resp.headers().get(header::LOCATION).unwrap(), location);
+        }
+
+        // should redirect if files listing is enabled
+        let srv = test::init_service(
+            App::new().service(
+                Files::new("/", ".")
+                    .show_files_listing()
+                    .redirect_to_slash_directory(),
+            ),
+        )
+        .await;
+        let req = TestRequest::with_uri("/tests").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+
+        // should not redirect if the path is wrong
+        let req = TestRequest::with_uri("/not_existing").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_directory_access_denied_status() {
+        let st = Files::new("/", ".").new_service(()).await.unwrap();
+        let req = TestRequest::with_uri("/tests").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let st = Files::new("/", ".")
+            .directory_access_denied_status(StatusCode::FORBIDDEN)
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/tests").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        // missing paths are still not found
+        let req = TestRequest::with_uri("/missing").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_files_negative_cache() {
+        let dir = env::temp_dir().join("actix-files-negative-cache");
+        fs::create_dir_all(&dir).unwrap();
+        let _ = fs::remove_file(dir.join("late.txt"));
+
+        let st = Files::new("/", &dir)
+            .negative_cache(16, Duration::from_millis(200))
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::with_uri("/late.txt").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the file is still reported missing until the entry expires
+        fs::write(dir.join("late.txt"), "here").unwrap();
+        let req = TestRequest::with_uri("/late.txt").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        actix_rt::time::sleep(Duration::from_millis(250)).await;
+        let req = TestRequest::with_uri("/late.txt").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_files_open_file_cache() {
+        let dir = env::temp_dir().join("actix-files-open-file-cache-service");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("app.js"), "v1").unwrap();
+
+        let st = Files::new("/", &dir)
+            .open_file_cache(8)
+            .open_file_cache_interval(Duration::from_secs(0))
+            .new_service(())
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let req = TestRequest::with_uri("/app.js").to_srv_request();
+            let resp = test::call_service(&st, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(test::read_body(resp).await, "v1");
+        }
+
+        // a file replaced by renaming another over it is opened again
+        fs::write(dir.join("app.js.new"), "v2").unwrap();
+        fs::rename(dir.join("app.js.new"), dir.join("app.js")).unwrap();
+        let req = TestRequest::with_uri("/app.js").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(test::read_body(resp).await, "v2");
+
+        // within the interval, cached files are served without looking up their path again
+        let st = Files::new("/", &dir)
+            .open_file_cache(8)
+            .open_file_cache_interval(Duration::from_secs(60))
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/app.js").to_srv_request();
+        test::call_service(&st, req).await;
+        fs::remove_file(dir.join("app.js")).unwrap();
+        let req = TestRequest::with_uri("/app.js").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(test::read_body(resp).await, "v2");
+    }
+
+    #[actix_rt::test]
+    async fn test_static_files_bad_directory() {
+        let service = Files::new("/", "./missing").new_service(()).await.unwrap();
+
+        let req = TestRequest::with_uri("/").to_srv_request();
+        let resp = test::call_service(&service, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_default_handler_file_missing() {
+        let st = Files::new("/", ".")
+            .default_handler(|req: ServiceRequest| {
+                ok(req.into_response(HttpResponse::Ok().body("default content")))
+            })
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/missing").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = test::read_body(resp).await;
+        assert_eq!(bytes, web::Bytes::from_static(b"default content"));
+    }
+
+    #[actix_rt::test]
+    async fn test_files_not_found_file() {
+        let st = Files::new("/", ".")
+            .not_found_file("tests/utf8.txt")
+            .default_handler(|req: ServiceRequest| {
+                ok(req.into_response(HttpResponse::Ok().body("default content")))
+            })
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::with_uri("/missing").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert!(!resp.headers().contains_key(header::ETAG));
+        assert!(!resp.headers().contains_key(header::LAST_MODIFIED));
+        let bytes = test::read_body(resp).await;
+        assert_eq!(bytes, fs::read("tests/utf8.txt").unwrap());
+
+        // never answered as if the missing file was cached
+        let req = TestRequest::with_uri("/missing")
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/Cargo.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // falls back to the default handler if the file is missing too
+        let st = Files::new("/", ".")
+            .not_found_file("tests/missing.html")
+            .default_handler(|req: ServiceRequest| {
+                ok(req.into_response(HttpResponse::Ok().body("default content")))
+            })
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/missing").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        let bytes = test::read_body(resp).await;
+        assert_eq!(bytes, web::Bytes::from_static(b"default content"));
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_index_nested() {
+        let service = Files::new(".", ".")
+            .index_file("lib.rs")
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default().uri("/src").to_srv_request();
+        let resp = test::call_service(&service, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/x-rust"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"lib.rs\""
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_index_missing() {
+        let service = Files::new(".", ".")
+            .index_file("index.html")
+            .new_service(())
+            .await
+            .unwrap();
+
+        // never falls back to a listing
+        let req = TestRequest::default().uri("/tests").to_srv_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let bytes = test::read_body(resp).await;
+        assert!(!format!("{:?}", bytes).contains("test.png"));
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_index_files() {
+        let service = Files::new(".", ".")
+            .index_files(&["index.html", "mod.rs", "lib.rs"])
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::default().uri("/src").to_srv_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"lib.rs\""
+        );
+
+        let req = TestRequest::default().uri("/tests").to_srv_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_serve_index() {
+        let srv = test::init_service(
+            App::new().service(Files::new("test", ".").index_file("Cargo.toml")),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/test").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = test::read_body(res).await;
+
+        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::get().uri("/test/").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = test::read_body(res).await;
+        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+        assert_eq!(bytes, data);
+
+        // nonexistent index file
+        let req = TestRequest::get().uri("/test/unknown").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::get().uri("/test/unknown/").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_percent_encoded() {
+        let srv = test::init_service(
+            App::new().service(Files::new("test", ".").index_file("Cargo.toml")),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/test/%43argo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_named_file() {
+        let srv =
+            test::init_service(App::new().service(NamedFile::open("Cargo.toml").unwrap()))
+                .await;
+
+        let req = TestRequest::get().uri("/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = test::read_body(res).await;
+        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::get().uri("/test/unknown").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_named_file_open_error() {
+        let path = env::temp_dir().join("actix-files-open-error.txt");
+        fs::write(&path, "removed").unwrap();
+
+        let srv = test::init_service(App::new().service(NamedFile::open(&path).unwrap())).await;
+        fs::remove_file(&path).unwrap();
+
+        let uri = format!("{}", path.display());
+        let req = TestRequest::get().uri(&uri).to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let status = |kind| named::open_error_status(&io::Error::from(kind));
+        assert_eq!(status(io::ErrorKind::NotFound), StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(io::ErrorKind::PermissionDenied),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(io::ErrorKind::InvalidInput),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_named_file_open_error_handler() {
+        let path = env::temp_dir().join("actix-files-open-error-handler.txt");
+        fs::write(&path, "removed").unwrap();
+
+        let file = NamedFile::open(&path).unwrap().on_open_error(|err, req| {
+            HttpResponse::Gone().body(format!("{:?} {}", err.kind(), req.path()))
+        });
+        let srv = test::init_service(App::new().service(file)).await;
+        fs::remove_file(&path).unwrap();
+
+        let uri = format!("{}", path.display());
+        let req = TestRequest::get().uri(&uri).to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::GONE);
+        let bytes = test::read_body(res).await;
+        assert_eq!(bytes, format!("NotFound {}", uri));
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_named_file_settings() {
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .set_status_code(StatusCode::ACCEPTED)
+            .set_content_type(mime::TEXT_PLAIN)
+            .set_attachment(Some("manifest.txt".to_owned()))
+            .set_expires(Duration::from_secs(60))
+            .use_etag(false)
+            .use_last_modified(false);
+        let srv = test::init_service(App::new().service(file)).await;
+
+        let req = TestRequest::get().uri("/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(
+            res.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"manifest.txt\""
+        );
+        assert!(res.headers().contains_key(header::EXPIRES));
+        assert!(!res.headers().contains_key(header::ETAG));
+        assert!(!res.headers().contains_key(header::LAST_MODIFIED));
+    }
+
+    #[actix_rt::test]
+    async fn test_serve_named_file_keep_open() {
+        let dir = env::temp_dir().join("actix-files-keep-open");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "first").unwrap();
+        let uri = format!("{}", path.display());
+
+        let file = NamedFile::open(&path).unwrap().keep_open(true);
+        let srv = test::init_service(App::new().service(file)).await;
+
+        // the handle opened up front is still served after the path is replaced
+        fs::write(dir.join("new.txt"), "second").unwrap();
+        fs::rename(dir.join("new.txt"), &path).unwrap();
+
+        for _ in 0..2 {
+            let req = TestRequest::get().uri(&uri).to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(test::read_body(res).await, "first");
+        }
+
+        let req = TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::RANGE, "bytes=1-3"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(test::read_body(res).await, "irs");
+
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .keep_open(true)
+            .revalidate_after(Duration::from_secs(0))
+            .set_max_age(Duration::from_secs(60))
+            .use_etag(false);
+        let srv = test::init_service(App::new().service(file)).await;
+
+        fs::write(dir.join("new.txt"), "third!").unwrap();
+        fs::rename(dir.join("new.txt"), &path).unwrap();
 
-        let response = srv.head("/tests/test.binary").send().await.unwrap();
+        // the reopened file keeps the settings
+        let req = TestRequest::get().uri(&uri).to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=60"
+        );
+        assert!(!res.headers().contains_key(header::ETAG));
+        assert_eq!(test::read_body(res).await, "third!");
 
-        let content_length = response
-            .headers()
-            .get(header::CONTENT_LENGTH)
-            .unwrap()
-            .to_str()
-            .unwrap();
+        fs::remove_file(&path).unwrap();
 
-        assert_eq!(content_length, "100");
+        let req = TestRequest::get().uri(&uri).to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
     }
 
     #[actix_rt::test]
-    async fn test_static_files_with_spaces() {
-        let srv = test::init_service(
-            App::new().service(Files::new("/", ".").index_file("Cargo.toml")),
-        )
-        .await;
-        let request = TestRequest::get()
-            .uri("/tests/test%20space.binary")
+    async fn test_serve_named_file_allowed_methods() {
+        let srv =
+            test::init_service(App::new().service(NamedFile::open("Cargo.toml").unwrap()))
+                .await;
+
+        let req = TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/Cargo.toml")
             .to_request();
-        let response = test::call_service(&srv, request).await;
-        assert_eq!(response.status(), StatusCode::OK);
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
 
-        let bytes = test::read_body(response).await;
-        let data = web::Bytes::from(fs::read("tests/test space.binary").unwrap());
-        assert_eq!(bytes, data);
-    }
+        for method in &[Method::POST, Method::PUT, Method::DELETE] {
+            let req = TestRequest::default()
+                .method(method.clone())
+                .uri("/Cargo.toml")
+                .to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(
+                res.headers().get(header::ALLOW).unwrap(),
+                "GET, HEAD, OPTIONS"
+            );
+            assert!(test::read_body(res).await.is_empty());
+        }
 
-    #[actix_rt::test]
-    async fn test_files_not_allowed() {
-        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/Cargo.toml")
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            res.headers().get(header::ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS"
+        );
+        assert_eq!(res.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+        assert!(test::read_body(res).await.is_empty());
 
+        // the advertised support for ranges matches that of `GET` requests
+        let file = NamedFile::open("Cargo.toml").unwrap().accept_ranges(false);
+        let srv = test::init_service(App::new().service(file)).await;
         let req = TestRequest::default()
+            .method(Method::OPTIONS)
             .uri("/Cargo.toml")
-            .method(Method::POST)
             .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(res.headers().get(header::ACCEPT_RANGES).is_none());
 
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .map_body(|chunk| chunk);
+        let srv = test::init_service(App::new().service(file)).await;
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/Cargo.toml")
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.headers().get(header::ACCEPT_RANGES).unwrap(), "none");
+
+        let file = NamedFile::open("Cargo.toml")
+            .unwrap()
+            .allowed_methods(&[Method::GET, Method::POST]);
+        let srv = test::init_service(App::new().service(file)).await;
+
+        let req = TestRequest::post().uri("/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
 
-        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
         let req = TestRequest::default()
-            .method(Method::PUT)
+            .method(Method::HEAD)
             .uri("/Cargo.toml")
             .to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers().get(header::ALLOW).unwrap(), "GET, POST");
     }
 
     #[actix_rt::test]
-    async fn test_files_guards() {
+    async fn test_serve_named_file_prefix() {
         let srv = test::init_service(
-            App::new().service(Files::new("/", ".").method_guard(guard::Post())),
+            App::new()
+                .service(web::scope("/test").service(NamedFile::open("Cargo.toml").unwrap())),
         )
         .await;
 
-        let req = TestRequest::default()
-            .uri("/Cargo.toml")
-            .method(Method::POST)
-            .to_request();
+        let req = TestRequest::get().uri("/test/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
 
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = test::read_body(res).await;
+        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+        assert_eq!(bytes, data);
+
+        let req = TestRequest::get().uri("/Cargo.toml").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
     }
 
     #[actix_rt::test]
-    async fn test_named_file_content_encoding() {
-        let srv = test::init_service(App::new().wrap(Compress::default()).service(
-            web::resource("/").to(|| async {
-                NamedFile::open("Cargo.toml")
-                    .unwrap()
-                    .set_content_encoding(header::ContentEncoding::Identity)
-            }),
-        ))
+    async fn test_serve_named_file_at() {
+        let srv = test::init_service(
+            App::new()
+                .service(NamedFile::open("Cargo.toml").unwrap().at("/"))
+                .service(
+                    web::scope("/test")
+                        .service(NamedFile::open("tests/test.png").unwrap().at("logo.png")),
+                ),
+        )
         .await;
 
-        let request = TestRequest::get()
-            .uri("/")
-            .insert_header((header::ACCEPT_ENCODING, "gzip"))
-            .to_request();
-        let res = test::call_service(&srv, request).await;
+        let req = TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&srv, req).await;
         assert_eq!(res.status(), StatusCode::OK);
-        assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+        let bytes = test::read_body(res).await;
+        assert_eq!(bytes, fs::read("Cargo.toml").unwrap());
+
+        let req = TestRequest::get().uri("/test/logo.png").to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+
+        // only the mount path is matched
+        for uri in &["/Cargo.toml", "/other", "/test/tests/test.png"] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    /// Calls `srv` with requests made by `req` until one is answered with a `Repr-Digest` header,
+    /// which is only sent once the file has been hashed on the blocking thread-pool.
+    async fn call_until_digest<S, R, B>(srv: &S, req: impl Fn() -> R) -> ServiceResponse<B>
+    where
+        S: Service<R, Response = ServiceResponse<B>, Error = Error>,
+    {
+        for _ in 0..100 {
+            let res = test::call_service(srv, req()).await;
+            if res.headers().contains_key("repr-digest") {
+                return res;
+            }
+
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("no digest was sent");
     }
 
     #[actix_rt::test]
-    async fn test_named_file_content_encoding_gzip() {
-        let srv = test::init_service(App::new().wrap(Compress::default()).service(
-            web::resource("/").to(|| async {
-                NamedFile::open("Cargo.toml")
+    async fn test_named_file_digest() {
+        let path = env::temp_dir().join("actix-files-digest.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let srv = test::init_service(
+            App::new().service(
+                NamedFile::open(&path)
                     .unwrap()
-                    .set_content_encoding(header::ContentEncoding::Gzip)
-            }),
-        ))
+                    .with_digest(DigestAlgo::Sha256)
+                    .at("/"),
+            ),
+        )
         .await;
 
-        let request = TestRequest::get()
-            .uri("/")
-            .insert_header((header::ACCEPT_ENCODING, "gzip"))
-            .to_request();
-        let res = test::call_service(&srv, request).await;
+        // the first response is sent before the file has been hashed
+        let req = TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&srv, req).await;
         assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get("repr-digest").is_none());
+
+        let res = call_until_digest(&srv, || TestRequest::get().uri("/").to_request()).await;
         assert_eq!(
-            res.headers()
-                .get(header::CONTENT_ENCODING)
-                .unwrap()
-                .to_str()
-                .unwrap(),
-            "gzip"
+            res.headers().get("repr-digest").unwrap(),
+            "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:"
+        );
+
+        // the digest is recomputed for a new version of the file
+        fs::write(&path, "hello, world").unwrap();
+        let res = call_until_digest(&srv, || TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(
+            res.headers().get("repr-digest").unwrap(),
+            "sha-256=:Ccp+TqpuiunH0mEWcSkYSINkTQffuny/vEyKLgg2DVs=:"
+        );
+
+        // digests are kept by path, so files opened by handlers benefit as well
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path)
+            .unwrap()
+            .with_digest(DigestAlgo::Sha256);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(
+            resp.headers().get("repr-digest").unwrap(),
+            "sha-256=:Ccp+TqpuiunH0mEWcSkYSINkTQffuny/vEyKLgg2DVs=:"
         );
+
+        let file = NamedFile::open(&path).unwrap();
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(resp.headers().get("repr-digest").is_none());
     }
 
     #[actix_rt::test]
-    async fn test_named_file_allowed_method() {
-        let req = TestRequest::default().method(Method::GET).to_http_request();
-        let file = NamedFile::open("Cargo.toml").unwrap();
-        let resp = file.respond_to(&req).await.unwrap();
-        assert_eq!(resp.status(), StatusCode::OK);
+    async fn test_named_file_digest_large() {
+        use sha2::Digest as _;
+
+        let path = env::temp_dir().join("actix-files-digest-large.bin");
+        let data = vec![7; 1024 * 1024 + 1];
+        fs::write(&path, &data).unwrap();
+        let digest = format!("sha-256=:{}:", base64::encode(sha2::Sha256::digest(&data)));
+
+        let req = TestRequest::default().to_http_request();
+        let mut sent = None;
+        for _ in 0..100 {
+            let file = NamedFile::open(&path)
+                .unwrap()
+                .with_digest(DigestAlgo::Sha256);
+            let resp = file.respond_to(&req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            sent = resp.headers().get("repr-digest").cloned();
+            if sent.is_some() {
+                break;
+            }
+
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(sent.unwrap(), digest.as_str());
     }
 
     #[actix_rt::test]
-    async fn test_static_files() {
-        let srv =
-            test::init_service(App::new().service(Files::new("/", ".").show_files_listing()))
-                .await;
-        let req = TestRequest::with_uri("/missing").to_request();
+    async fn test_named_file_reader_transform() {
+        let data = fs::read("tests/test.binary").unwrap();
+        let xor = |chunk: &[u8]| chunk.iter().map(|b| b ^ 0x5a).collect::<Vec<_>>();
 
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(16)
+            .with_reader_transform(move |chunk| {
+                // every chunk but the last is a whole buffer
+                assert!(chunk.len() == 16 || chunk.len() == 100 % 16);
+                xor(chunk)
+            });
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "none");
+        assert_eq!(resp.body().size(), BodySize::Stream);
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, xor(&data));
 
-        let srv = test::init_service(App::new().service(Files::new("/", "."))).await;
+        // a known output length is sent, here for a transform dropping the first byte of the file
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_buffer_size(200)
+            .with_reader_transform(|chunk| chunk[1..].to_vec())
+            .set_transformed_length(99);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.body().size(), BodySize::Sized(99));
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        assert_eq!(bytes, data[1..]);
+    }
 
-        let req = TestRequest::default().to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    #[actix_rt::test]
+    async fn test_named_file_small_file_threshold() {
+        let data = fs::read("tests/test.binary").unwrap();
 
-        let srv =
-            test::init_service(App::new().service(Files::new("/", ".").show_files_listing()))
-                .await;
-        let req = TestRequest::with_uri("/tests").to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(
-            resp.headers().get(header::CONTENT_TYPE).unwrap(),
-            "text/html; charset=utf-8"
-        );
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .small_file_threshold(1024);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(matches!(resp.body(), Body::Bytes(ref body) if *body == data));
 
-        let bytes = test::read_body(resp).await;
-        assert!(format!("{:?}", bytes).contains("/tests/test.png"));
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=10-20"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .small_file_threshold(1024);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(matches!(resp.body(), Body::Bytes(ref body) if *body == data[10..=20]));
+
+        // files as large as the threshold are streamed
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .small_file_threshold(100);
+        let resp = file.respond_to(&req).await.unwrap();
+        assert!(matches!(resp.body(), Body::Message(_)));
+        assert_eq!(resp.body().size(), BodySize::Sized(100));
     }
 
     #[actix_rt::test]
-    async fn test_redirect_to_slash_directory() {
-        // should not redirect if no index and files listing is disabled
+    async fn test_named_file_requested_digest() {
+        let path = env::temp_dir().join("actix-files-requested-digest.txt");
+        fs::write(&path, "hello").unwrap();
+
         let srv = test::init_service(
-            App::new().service(Files::new("/", ".").redirect_to_slash_directory()),
+            App::new().service(
+                NamedFile::open(&path)
+                    .unwrap()
+                    .with_requested_digest()
+                    .at("/"),
+            ),
         )
         .await;
-        let req = TestRequest::with_uri("/tests").to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
-        // should redirect if index present
+        let sha256 = "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:".to_owned();
+        let sha512 = format!(
+            "sha-512=:{}:",
+            base64::encode(<sha2::Sha512 as sha2::Digest>::digest(b"hello"))
+        );
+
+        let cases = [
+            (None, None),
+            (
+                Some(("want-repr-digest", "sha-256=3, sha-512=8")),
+                Some(&sha512),
+            ),
+            (
+                Some(("want-repr-digest", "sha-512=0, sha-256=1")),
+                Some(&sha256),
+            ),
+            (
+                Some(("want-digest", "SHA-512;q=0.3, SHA-256")),
+                Some(&sha256),
+            ),
+            (Some(("want-digest", "md5")), None),
+        ];
+
+        for (want, expected) in cases.iter() {
+            let req = || {
+                let mut req = TestRequest::get().uri("/");
+                if let Some(want) = want {
+                    req = req.insert_header(*want);
+                }
+                req.to_request()
+            };
+
+            let res = match expected {
+                Some(_) => call_until_digest(&srv, req).await,
+                None => test::call_service(&srv, req()).await,
+            };
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                res.headers()
+                    .get("repr-digest")
+                    .map(|digest| digest.to_str().unwrap().to_owned()),
+                expected.cloned(),
+                "{:?}",
+                want
+            );
+
+            let vary: Vec<_> = res.headers().get_all(header::VARY).collect();
+            assert_eq!(vary, vec!["Want-Repr-Digest, Want-Digest"]);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_try_into_response() {
+        let path = env::temp_dir().join("actix-files-try-into-response.txt");
+        fs::write(&path, "hello, world").unwrap();
+
+        let req = TestRequest::default().to_http_request();
+        let file = NamedFile::open(&path).unwrap().small_file_threshold(1024);
+        let resp = file.try_into_response(&req).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // files truncated since they were opened can not be read in full
+        let small = NamedFile::open(&path).unwrap().small_file_threshold(1024);
+        let small2 = NamedFile::open(&path).unwrap().small_file_threshold(1024);
+        fs::write(&path, "hello").unwrap();
+
+        assert!(small.try_into_response(&req).is_err());
+        assert_eq!(
+            small2.into_response(&req).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_map_body() {
+        let req = TestRequest::default()
+            .insert_header((header::RANGE, "bytes=0-4"))
+            .to_http_request();
+        let file = NamedFile::open("tests/test.binary")
+            .unwrap()
+            .with_digest(DigestAlgo::Sha256)
+            .map_body(|chunk| Bytes::from(chunk.iter().map(|b| !b).collect::<Vec<_>>()));
+        let resp = file.respond_to(&req).await.unwrap();
+
+        // the range is ignored since it cannot be applied to the transformed body
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "none");
+        assert!(resp.headers().get(header::CONTENT_RANGE).is_none());
+        assert!(resp.headers().get("repr-digest").is_none());
+        assert!(resp.headers().get(header::ETAG).is_some());
+        assert_eq!(resp.body().size(), BodySize::Stream);
+
+        let bytes = test::load_body(resp.into_body()).await.unwrap();
+        let data: Vec<u8> = fs::read("tests/test.binary")
+            .unwrap()
+            .iter()
+            .map(|b| !b)
+            .collect();
+        assert_eq!(bytes, data);
+    }
+
+    #[actix_rt::test]
+    async fn test_named_file_default_service() {
         let srv = test::init_service(
-            App::new().service(
-                Files::new("/", ".")
-                    .index_file("test.png")
-                    .redirect_to_slash_directory(),
-            ),
+            App::new().default_service(NamedFile::open("Cargo.toml").unwrap()),
         )
         .await;
-        let req = TestRequest::with_uri("/tests").to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::FOUND);
 
-        // should redirect if files listing is enabled
-        let srv = test::init_service(
-            App::new().service(
-                Files::new("/", ".")
-                    .show_files_listing()
-                    .redirect_to_slash_directory(),
-            ),
-        )
-        .await;
-        let req = TestRequest::with_uri("/tests").to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::FOUND);
+        for route in ["/foobar", "/baz", "/"].iter() {
+            let req = TestRequest::get().uri(route).to_request();
+            let res = test::call_service(&srv, req).await;
+            assert_eq!(res.status(), StatusCode::OK);
 
-        // should not redirect if the path is wrong
-        let req = TestRequest::with_uri("/not_existing").to_request();
-        let resp = test::call_service(&srv, req).await;
-        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            let bytes = test::read_body(res).await;
+            let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+            assert_eq!(bytes, data);
+        }
     }
 
     #[actix_rt::test]
-    async fn test_static_files_bad_directory() {
-        let service = Files::new("/", "./missing").new_service(()).await.unwrap();
-
-        let req = TestRequest::with_uri("/").to_srv_request();
-        let resp = test::call_service(&service, req).await;
+    async fn test_default_handler_named_file() {
+        let st = Files::new("/", ".")
+            .default_handler(NamedFile::open("Cargo.toml").unwrap())
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/missing").to_srv_request();
+        let resp = test::call_service(&st, req).await;
 
-        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = test::read_body(resp).await;
+        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
+        assert_eq!(bytes, data);
     }
 
     #[actix_rt::test]
-    async fn test_default_handler_file_missing() {
+    async fn test_default_handler_index() {
         let st = Files::new("/", ".")
+            .index_file("Cargo.toml")
             .default_handler(|req: ServiceRequest| {
                 ok(req.into_response(HttpResponse::Ok().body("default content")))
             })
             .new_service(())
             .await
             .unwrap();
-        let req = TestRequest::with_uri("/missing").to_srv_request();
+
+        // the index file takes precedence
+        let req = TestRequest::with_uri("/").to_srv_request();
         let resp = test::call_service(&st, req).await;
+        let bytes = test::read_body(resp).await;
+        assert_eq!(bytes, Bytes::from(fs::read("Cargo.toml").unwrap()));
 
-        assert_eq!(resp.status(), StatusCode::OK);
+        // directories without the index file fall back to the default handler
+        let req = TestRequest::with_uri("/tests").to_srv_request();
+        let resp = test::call_service(&st, req).await;
         let bytes = test::read_body(resp).await;
-        assert_eq!(bytes, web::Bytes::from_static(b"default content"));
+        assert_eq!(bytes, Bytes::from_static(b"default content"));
     }
 
     #[actix_rt::test]
-    async fn test_serve_index_nested() {
-        let service = Files::new(".", ".")
-            .index_file("lib.rs")
+    async fn test_files_cache_control() {
+        let st = Files::new("/", ".")
+            .cache_control(header::CacheControl(vec![header::CacheDirective::NoCache]))
+            .use_etag(false)
             .new_service(())
             .await
             .unwrap();
 
-        let req = TestRequest::default().uri("/src").to_srv_request();
-        let resp = test::call_service(&service, req).await;
-
+        let req = TestRequest::with_uri("/Cargo.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
         assert_eq!(
-            resp.headers().get(header::CONTENT_TYPE).unwrap(),
-            "text/x-rust"
-        );
-        assert_eq!(
-            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
-            "inline; filename=\"lib.rs\""
+            resp.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
         );
+        assert!(resp.headers().get(header::ETAG).is_none());
+        assert!(resp.headers().get(header::LAST_MODIFIED).is_some());
     }
 
     #[actix_rt::test]
-    async fn integration_serve_index() {
+    async fn test_files_strip_prefix_segments() {
         let srv = test::init_service(
-            App::new().service(Files::new("test", ".").index_file("Cargo.toml")),
+            App::new().service(Files::new("/assets", ".").strip_prefix_segments(1)),
         )
         .await;
 
-        let req = TestRequest::get().uri("/test").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::OK);
-
-        let bytes = test::read_body(res).await;
-
-        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-        assert_eq!(bytes, data);
-
-        let req = TestRequest::get().uri("/test/").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::OK);
-
-        let bytes = test::read_body(res).await;
-        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-        assert_eq!(bytes, data);
-
-        // nonexistent index file
-        let req = TestRequest::get().uri("/test/unknown").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::NOT_FOUND);
-
-        let req = TestRequest::get().uri("/test/unknown/").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::NOT_FOUND);
-    }
+        let req = TestRequest::with_uri("/assets/v123/tests/test.png").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
 
-    #[actix_rt::test]
-    async fn integration_percent_encoded() {
-        let srv = test::init_service(
-            App::new().service(Files::new("test", ".").index_file("Cargo.toml")),
-        )
-        .await;
+        let req = TestRequest::with_uri("/assets/tests/test.png").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
-        let req = TestRequest::get().uri("/test/%43argo.toml").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::OK);
+        let req = TestRequest::with_uri("/assets/v123").to_request();
+        let resp = test::call_service(&srv, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
 
     #[actix_rt::test]
-    async fn test_serve_named_file() {
-        let srv =
-            test::init_service(App::new().service(NamedFile::open("Cargo.toml").unwrap()))
-                .await;
-
-        let req = TestRequest::get().uri("/Cargo.toml").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::OK);
+    async fn test_files_default_content_type() {
+        let st = Files::new("/", "tests")
+            .default_content_type(mime::TEXT_PLAIN_UTF_8)
+            .new_service(())
+            .await
+            .unwrap();
 
-        let bytes = test::read_body(res).await;
-        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-        assert_eq!(bytes, data);
+        let req = TestRequest::with_uri("/test.binary").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "inline; filename=\"test.binary\""
+        );
 
-        let req = TestRequest::get().uri("/test/unknown").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = TestRequest::with_uri("/test.png").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
     }
 
     #[actix_rt::test]
-    async fn test_serve_named_file_prefix() {
-        let srv = test::init_service(
-            App::new()
-                .service(web::scope("/test").service(NamedFile::open("Cargo.toml").unwrap())),
-        )
-        .await;
+    async fn test_files_content_type_override() {
+        let dir = env::temp_dir().join("actix-files-content-type-override");
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["app.wasm", "LICENSE", "secret"] {
+            fs::write(dir.join(name), "data").unwrap();
+        }
 
-        let req = TestRequest::get().uri("/test/Cargo.toml").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::OK);
+        let st = Files::new("/", &dir)
+            .default_content_type(mime::TEXT_PLAIN_UTF_8)
+            .content_type_override(|path| {
+                if path.extension() == Some("wasm".as_ref()) {
+                    Some("application/x-custom-wasm".parse().unwrap())
+                } else if path.ends_with("secret") {
+                    Some(mime::APPLICATION_OCTET_STREAM)
+                } else {
+                    None
+                }
+            })
+            .new_service(())
+            .await
+            .unwrap();
 
-        let bytes = test::read_body(res).await;
-        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-        assert_eq!(bytes, data);
+        let content_type = |uri| {
+            let st = &st;
+            async move {
+                let req = TestRequest::with_uri(uri).to_srv_request();
+                let resp = test::call_service(st, req).await;
+                resp.headers().get(header::CONTENT_TYPE).unwrap().clone()
+            }
+        };
 
-        let req = TestRequest::get().uri("/Cargo.toml").to_request();
-        let res = test::call_service(&srv, req).await;
-        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(content_type("/app.wasm").await, "application/x-custom-wasm");
+
+        // files the callback returns `None` for fall back to the default content type, which does
+        // not replace an overridden type
+        assert_eq!(content_type("/LICENSE").await, "text/plain; charset=utf-8");
+        assert_eq!(content_type("/secret").await, "application/octet-stream");
     }
 
     #[actix_rt::test]
-    async fn test_named_file_default_service() {
-        let srv = test::init_service(
-            App::new().default_service(NamedFile::open("Cargo.toml").unwrap()),
-        )
-        .await;
-
-        for route in ["/foobar", "/baz", "/"].iter() {
-            let req = TestRequest::get().uri(route).to_request();
-            let res = test::call_service(&srv, req).await;
-            assert_eq!(res.status(), StatusCode::OK);
+    async fn test_files_response_headers() {
+        let st = Files::new("/", ".")
+            .show_files_listing()
+            .add_response_header((
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static("default-src 'self'"),
+            ))
+            .add_response_header((header::LINK, HeaderValue::from_static("</a>; rel=a")))
+            .add_response_header((header::LINK, HeaderValue::from_static("</b>; rel=b")))
+            .add_response_header((header::CONTENT_TYPE, HeaderValue::from_static("text/html")))
+            .new_service(())
+            .await
+            .unwrap();
 
-            let bytes = test::read_body(res).await;
-            let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-            assert_eq!(bytes, data);
+        for uri in &["/Cargo.toml", "/tests/"] {
+            let req = TestRequest::with_uri(uri).to_srv_request();
+            let resp = test::call_service(&st, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers().get(header::CONTENT_SECURITY_POLICY).unwrap(),
+                "default-src 'self'"
+            );
+            assert_eq!(resp.headers().get_all(header::LINK).count(), 2);
         }
+
+        // headers set by the file are kept
+        let req = TestRequest::with_uri("/Cargo.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        let content_types: Vec<_> = resp.headers().get_all(header::CONTENT_TYPE).collect();
+        assert_eq!(content_types, vec!["text/x-toml"]);
     }
 
     #[actix_rt::test]
-    async fn test_default_handler_named_file() {
+    async fn test_path_filter() {
         let st = Files::new("/", ".")
-            .default_handler(NamedFile::open("Cargo.toml").unwrap())
+            .path_filter(|path, _| path.extension() != Some("png".as_ref()))
             .new_service(())
             .await
             .unwrap();
-        let req = TestRequest::with_uri("/missing").to_srv_request();
+
+        let req = TestRequest::with_uri("/tests/test.png").to_srv_request();
         let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
+        let req = TestRequest::with_uri("/tests/test.binary").to_srv_request();
+        let resp = test::call_service(&st, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
-        let bytes = test::read_body(resp).await;
-        let data = Bytes::from(fs::read("Cargo.toml").unwrap());
-        assert_eq!(bytes, data);
+    }
+
+    #[actix_rt::test]
+    async fn test_hidden_files() {
+        let st = Files::new("/", ".").new_service(()).await.unwrap();
+
+        let req = TestRequest::with_uri("/.git/config").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/tests/.hidden").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
 
     #[actix_rt::test]
@@ -856,4 +3586,100 @@ mod tests {
             "inline; filename=\"symlink-test.png\""
         );
     }
+
+    #[actix_rt::test]
+    async fn test_path_traversal() {
+        let st = Files::new("/", "tests").new_service(()).await.unwrap();
+
+        for uri in &[
+            "/../Cargo.toml",
+            "/%2e%2e/Cargo.toml",
+            "/%2E%2E/%2e%2e/actix-files/Cargo.toml",
+            "/fixtures/../../Cargo.toml",
+            "/..%2fCargo.toml",
+            "/..%5cCargo.toml",
+            "/%2fCargo.toml",
+        ] {
+            let req = TestRequest::with_uri(uri).to_srv_request();
+            let resp = test::call_service(&st, req).await;
+            assert!(
+                resp.status().is_client_error(),
+                "{} answered with {}",
+                uri,
+                resp.status()
+            );
+        }
+
+        let req = TestRequest::with_uri("/fixtures/../test.png").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[cfg(unix)]
+    #[actix_rt::test]
+    async fn test_symlink_escape() {
+        let root = env::temp_dir().join("actix-files-symlink-escape");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir(&root).unwrap();
+        std::os::unix::fs::symlink(
+            fs::canonicalize("Cargo.toml").unwrap(),
+            root.join("escape.toml"),
+        )
+        .unwrap();
+        fs::write(root.join("file.toml"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("file.toml"), root.join("inner.toml")).unwrap();
+
+        // followed by default
+        let st = Files::new("/", &root).new_service(()).await.unwrap();
+        let req = TestRequest::with_uri("/escape.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let st = Files::new("/", &root)
+            .restrict_symlinks()
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/escape.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // symlinks that stay inside the directory are still followed
+        let req = TestRequest::with_uri("/inner.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let st = Files::new("/", &root)
+            .disable_symlinks()
+            .new_service(())
+            .await
+            .unwrap();
+        let req = TestRequest::with_uri("/escape.toml").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_disable_symlinks() {
+        let st = Files::new("/", ".")
+            .disable_symlinks()
+            .index_file("symlink-test.png")
+            .new_service(())
+            .await
+            .unwrap();
+
+        let req = TestRequest::with_uri("/tests/symlink-test.png").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/tests").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::with_uri("/tests/test.png").to_srv_request();
+        let resp = test::call_service(&st, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, path::Path};
+
+use mime::Mime;
+
+/// A set of MIME types to use for file extensions in place of the ones guessed from the file name.
+///
+/// Extensions are matched case-insensitively and without the leading dot.
+///
+/// # Examples
+/// ```
+/// use actix_files::{MimeOverride, NamedFile};
+///
+/// let overrides = MimeOverride::new()
+///     .add("webmanifest", "application/manifest+json".parse().unwrap())
+///     .add("wasm", "application/wasm".parse().unwrap());
+///
+/// let file = NamedFile::open_with_mime_override("./static/app.webmanifest", &overrides);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MimeOverride {
+    types: HashMap<String, Mime>,
+}
+
+impl MimeOverride {
+    /// Creates an empty set of overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `mime` for files with the extension `ext`, replacing any previous override for it.
+    pub fn add(mut self, ext: &str, mime: Mime) -> Self {
+        self.types.insert(ext.to_ascii_lowercase(), mime);
+        self
+    }
+
+    /// Returns the MIME type set for the extension `ext`, if any.
+    pub fn get(&self, ext: &str) -> Option<&Mime> {
+        self.types.get(&ext.to_ascii_lowercase())
+    }
+
+    /// Returns the MIME type set for the extension of `path`, if any.
+    pub(crate) fn get_for_path(&self, path: &Path) -> Option<&Mime> {
+        self.get(path.extension()?.to_str()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_for_path() {
+        let overrides = MimeOverride::new().add("WASM", "application/wasm".parse().unwrap());
+
+        let wasm = |path| {
+            overrides
+                .get_for_path(Path::new(path))
+                .map(Mime::essence_str)
+        };
+
+        assert_eq!(wasm("static/app.wasm"), Some("application/wasm"));
+        assert_eq!(wasm("app.Wasm"), Some("application/wasm"));
+        assert!(overrides.get_for_path(Path::new("app.js")).is_none());
+        assert!(overrides.get_for_path(Path::new("wasm")).is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    cmp,
+    convert::TryFrom,
+    fs::File,
+    io,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use actix_web::error::Error;
+use bytes::Bytes;
+use futures_core::Stream;
+use memmap2::{Mmap, MmapOptions};
+
+use crate::chunked::BytesSentFn;
+
+/// A body stream over a memory-mapped region of a file.
+pub(crate) struct MmapStream {
+    map: Mmap,
+    pos: usize,
+    buffer_size: usize,
+    bytes_sent_fn: Option<Rc<BytesSentFn>>,
+}
+
+impl MmapStream {
+    /// Maps `length` bytes of `file` starting at `offset`.
+    ///
+    /// # Safety
+    /// The mapped region must not be truncated while the stream is alive; doing so makes reading
+    /// it raise `SIGBUS` on Unix.
+    pub(crate) unsafe fn new(file: &File, offset: u64, length: u64) -> io::Result<Self> {
+        let length = usize::try_from(length)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "region too large to map"))?;
+
+        let map = MmapOptions::new().offset(offset).len(length).map(file)?;
+
+        Ok(Self {
+            map,
+            pos: 0,
+            buffer_size: crate::chunked::DEFAULT_BUFFER_SIZE,
+            bytes_sent_fn: None,
+        })
+    }
+
+    /// Sets the maximum number of bytes copied for each chunk.
+    pub(crate) fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets the function called with the number of bytes sent so far after each chunk, and once
+    /// more when the stream is dropped.
+    pub(crate) fn bytes_sent_fn(mut self, bytes_sent_fn: Option<Rc<BytesSentFn>>) -> Self {
+        self.bytes_sent_fn = bytes_sent_fn;
+        self
+    }
+}
+
+impl Drop for MmapStream {
+    fn drop(&mut self) {
+        if let Some(ref bytes_sent_fn) = self.bytes_sent_fn {
+            bytes_sent_fn(self.pos as u64);
+        }
+    }
+}
+
+impl Stream for MmapStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.pos == this.map.len() {
+            return Poll::Ready(None);
+        }
+
+        let end = cmp::min(this.pos + this.buffer_size, this.map.len());
+        let chunk = Bytes::copy_from_slice(&this.map[this.pos..end]);
+        this.pos = end;
+
+        if let Some(ref bytes_sent_fn) = this.bytes_sent_fn {
+            bytes_sent_fn(this.pos as u64);
+        }
+
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
@@ -1,43 +1,155 @@
 use actix_service::{Service, ServiceFactory};
-use actix_utils::future::{ok, ready, Ready};
+use actix_utils::future::{ok, Ready};
 use actix_web::dev::{AppService, HttpServiceFactory, ResourceDef};
-use std::fs::{File, Metadata};
+use std::cell::RefCell;
+use std::cmp;
+use std::fmt;
+use std::fs::{self, File, Metadata};
 use std::io;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
 
 use actix_web::{
     dev::{BodyEncoding, ServiceRequest, ServiceResponse, SizedStream},
+    error,
     http::{
         header::{
             self, Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue,
         },
-        ContentEncoding, StatusCode,
+        ContentEncoding, HeaderMap, HeaderName, Method, StatusCode,
     },
-    Error, HttpMessage, HttpRequest, HttpResponse, Responder,
+    web, Error, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
 };
 use bitflags::bitflags;
+use bytes::Bytes;
+use futures_core::Stream;
 use mime_guess::from_path;
 
-use crate::ChunkedReadFile;
-use crate::{encoding::equiv_utf8_text, range::HttpRange};
+#[cfg(feature = "mmap")]
+use crate::mmap::MmapStream;
+use crate::{
+    chunked::{self, BytesSentFn, ChangeCheck, DEFAULT_BUFFER_SIZE},
+    digest::{preferred_algo, DigestCache, DigestKey, DigestMode},
+    sniff::{self, SNIFF_LEN},
+    ChunkedReadFile, DigestAlgo, MimeOverride,
+};
+use crate::{
+    encoding::{precompressed_variants, with_charset},
+    range::{self, HttpRange, HttpRangeError},
+};
 
 bitflags! {
-    pub(crate) struct Flags: u8 {
-        const ETAG =                0b0000_0001;
-        const LAST_MD =             0b0000_0010;
-        const CONTENT_DISPOSITION = 0b0000_0100;
-        const PREFER_UTF8 =         0b0000_1000;
+    pub(crate) struct Flags: u32 {
+        const ETAG =                0b0000_0000_0000_0000_0001;
+        const LAST_MD =             0b0000_0000_0000_0000_0010;
+        const CONTENT_DISPOSITION = 0b0000_0000_0000_0000_0100;
+        const PREFER_UTF8 =         0b0000_0000_0000_0000_1000;
+        const ETAG_NO_INODE =       0b0000_0000_0000_0001_0000;
+        const WEAK_ETAG =           0b0000_0000_0000_0010_0000;
+        const COALESCE_RANGES =     0b0000_0000_0000_0100_0000;
+        const IGNORE_BAD_RANGE =    0b0000_0000_0000_1000_0000;
+        const ACCEPT_RANGES =       0b0000_0000_0001_0000_0000;
+        const DATE =                0b0000_0000_0010_0000_0000;
+        const KEEP_OPEN =           0b0000_0000_0100_0000_0000;
+        const USE_MMAP =            0b0000_0000_1000_0000_0000;
+        const CUSTOM_DISPOSITION =  0b0000_0001_0000_0000_0000;
+        const ADVERTISE_RANGES =    0b0000_0010_0000_0000_0000;
+        const EMPTY_NO_CONTENT =    0b0000_0100_0000_0000_0000;
+        const ABORT_ON_CHANGE =     0b0000_1000_0000_0000_0000;
+        const CONDITIONAL_CUSTOM =  0b0001_0000_0000_0000_0000;
+        const ENCODED_RANGES =      0b0010_0000_0000_0000_0000;
     }
 }
 
 impl Default for Flags {
     fn default() -> Self {
-        Flags::from_bits_truncate(0b0000_0111)
+        Flags::from_bits_truncate(0b0001_0010_0011_0100_0111)
+    }
+}
+
+/// When to send the `Content-Disposition` header of a [`NamedFile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispositionPolicy {
+    /// Always send the header. This is the default.
+    Always,
+
+    /// Only send the header for files served as attachments, leaving it off for `inline` ones.
+    NeverForInline,
+
+    /// Never send the header.
+    Never,
+}
+
+impl Default for DispositionPolicy {
+    fn default() -> Self {
+        DispositionPolicy::Always
+    }
+}
+
+/// Options applied to a [`NamedFile`] when it is opened with [`NamedFile::open_with`].
+///
+/// Each option has the same effect and default as the `NamedFile` method of the same name. The
+/// options can be built once and shared by every file served with the same policy.
+///
+/// # Examples
+/// ```
+/// use actix_files::{DispositionPolicy, NamedFile, NamedFileOptions};
+///
+/// let options = NamedFileOptions::new()
+///     .prefer_utf8(true)
+///     .use_etag(false)
+///     .set_disposition_policy(DispositionPolicy::NeverForInline);
+///
+/// let file = NamedFile::open_with("./static/index.html", &options);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NamedFileOptions {
+    flags: Flags,
+    disposition_policy: DispositionPolicy,
+    cache_control: Option<header::CacheControl>,
+}
+
+impl NamedFileOptions {
+    /// Creates options with the defaults of [`NamedFile::open`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`NamedFile::use_etag`].
+    pub fn use_etag(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ETAG, value);
+        self
+    }
+
+    /// See [`NamedFile::use_last_modified`].
+    pub fn use_last_modified(mut self, value: bool) -> Self {
+        self.flags.set(Flags::LAST_MD, value);
+        self
+    }
+
+    /// See [`NamedFile::prefer_utf8`].
+    pub fn prefer_utf8(mut self, value: bool) -> Self {
+        self.flags.set(Flags::PREFER_UTF8, value);
+        self
+    }
+
+    /// See [`NamedFile::set_disposition_policy`].
+    pub fn set_disposition_policy(mut self, policy: DispositionPolicy) -> Self {
+        self.disposition_policy = policy;
+        self
+    }
+
+    /// See [`NamedFile::set_cache_control`].
+    pub fn set_cache_control(mut self, cache_control: header::CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
     }
 }
 
@@ -65,17 +177,88 @@ impl Default for Flags {
 ///     NamedFile::open("./static/index.html")
 /// }
 /// ```
-#[derive(Debug)]
 pub struct NamedFile {
     path: PathBuf,
     file: File,
-    modified: Option<SystemTime>,
+    pub(crate) modified: Option<SystemTime>,
     pub(crate) md: Metadata,
     pub(crate) flags: Flags,
     pub(crate) status_code: StatusCode,
     pub(crate) content_type: mime::Mime,
     pub(crate) content_disposition: header::ContentDisposition,
+    pub(crate) disposition_policy: DispositionPolicy,
     pub(crate) encoding: Option<ContentEncoding>,
+    pub(crate) cache_control: Option<header::CacheControl>,
+    pub(crate) expires: Option<Duration>,
+    pub(crate) etag_fn: Option<Rc<EtagFn>>,
+    pub(crate) open_error_handler: Option<Rc<OpenErrorHandler>>,
+    pub(crate) revalidate_after: Option<Duration>,
+    pub(crate) allowed_methods: Option<Vec<Method>>,
+    pub(crate) bytes_sent_fn: Option<Rc<BytesSentFn>>,
+    pub(crate) buffer_size: usize,
+    pub(crate) read_ahead: usize,
+    pub(crate) small_file_threshold: u64,
+    pub(crate) content_length: Option<u64>,
+    pub(crate) range: Option<HttpRange>,
+    pub(crate) precompressed: Option<ContentEncoding>,
+    pub(crate) charset: Option<header::Charset>,
+    pub(crate) content_language: Option<header::LanguageTag>,
+    pub(crate) max_ranges: usize,
+    pub(crate) change_check_interval: Duration,
+    pub(crate) mount_path: Option<String>,
+    pub(crate) digest: Option<DigestMode>,
+    pub(crate) body_map: Option<Rc<BodyMapFn>>,
+    pub(crate) transformed_length: Option<u64>,
+}
+
+/// Default limit on the number of ranges in a single `Range` header.
+const DEFAULT_MAX_RANGES: usize = 10;
+
+/// Longest time ahead sent in an `Expires` header; dates further out cannot always be formatted.
+const MAX_EXPIRES: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Default time between checks for changes to a file being served.
+const DEFAULT_CHANGE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) type EtagFn = dyn Fn(&Metadata, &Path) -> Option<header::EntityTag>;
+
+pub(crate) type OpenErrorHandler = dyn Fn(io::Error, &HttpRequest) -> HttpResponse;
+
+pub(crate) type BodyMapFn = dyn Fn(Bytes) -> Bytes;
+
+impl fmt::Debug for NamedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NamedFile")
+            .field("path", &self.path)
+            .field("file", &self.file)
+            .field("modified", &self.modified)
+            .field("md", &self.md)
+            .field("flags", &self.flags)
+            .field("status_code", &self.status_code)
+            .field("content_type", &self.content_type)
+            .field("content_disposition", &self.content_disposition)
+            .field("disposition_policy", &self.disposition_policy)
+            .field("encoding", &self.encoding)
+            .field("cache_control", &self.cache_control)
+            .field("expires", &self.expires)
+            .field("revalidate_after", &self.revalidate_after)
+            .field("allowed_methods", &self.allowed_methods)
+            .field("buffer_size", &self.buffer_size)
+            .field("read_ahead", &self.read_ahead)
+            .field("small_file_threshold", &self.small_file_threshold)
+            .field("content_length", &self.content_length)
+            .field("range", &self.range)
+            .field("precompressed", &self.precompressed)
+            .field("charset", &self.charset)
+            .field("content_language", &self.content_language)
+            .field("max_ranges", &self.max_ranges)
+            .field("change_check_interval", &self.change_check_interval)
+            .field("mount_path", &self.mount_path)
+            .field("digest", &self.digest)
+            .field("body_map", &self.body_map.is_some())
+            .field("transformed_length", &self.transformed_length)
+            .finish()
+    }
 }
 
 impl NamedFile {
@@ -101,6 +284,33 @@ impl NamedFile {
     /// }
     /// ```
     pub fn from_file<P: AsRef<Path>>(file: File, path: P) -> io::Result<NamedFile> {
+        let md = file.metadata()?;
+        Self::from_file_with_metadata(file, path, md)
+    }
+
+    /// Creates an instance from a previously opened file and its metadata.
+    ///
+    /// This is the same as [`NamedFile::from_file`] but avoids querying the file's metadata again
+    /// when it is already at hand, e.g. from an earlier directory scan.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use std::fs::File;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = File::open("Cargo.toml")?;
+    /// let md = file.metadata()?;
+    /// let named_file = NamedFile::from_file_with_metadata(file, "Cargo.toml", md)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_file_with_metadata<P: AsRef<Path>>(
+        file: File,
+        path: P,
+        md: Metadata,
+    ) -> io::Result<NamedFile> {
         let path = path.as_ref().to_path_buf();
 
         // Get the name of the file and use it to construct default Content-Type
@@ -118,31 +328,16 @@ impl NamedFile {
 
             let ct = from_path(&path).first_or_octet_stream();
 
-            let disposition = match ct.type_() {
-                mime::IMAGE | mime::TEXT | mime::VIDEO => DispositionType::Inline,
-                _ => DispositionType::Attachment,
-            };
-
-            let mut parameters =
-                vec![DispositionParam::Filename(String::from(filename.as_ref()))];
-
-            if !filename.is_ascii() {
-                parameters.push(DispositionParam::FilenameExt(ExtendedValue {
-                    charset: Charset::Ext(String::from("UTF-8")),
-                    language_tag: None,
-                    value: filename.into_owned().into_bytes(),
-                }))
-            }
+            let disposition = default_disposition(&ct);
 
             let cd = ContentDisposition {
                 disposition,
-                parameters,
+                parameters: filename_params(&filename),
             };
 
             (ct, cd)
         };
 
-        let md = file.metadata()?;
         let modified = md.modified().ok();
         let encoding = None;
 
@@ -151,9 +346,31 @@ impl NamedFile {
             file,
             content_type,
             content_disposition,
+            disposition_policy: DispositionPolicy::default(),
             md,
             modified,
             encoding,
+            cache_control: None,
+            expires: None,
+            etag_fn: None,
+            open_error_handler: None,
+            revalidate_after: None,
+            allowed_methods: None,
+            bytes_sent_fn: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            read_ahead: 0,
+            small_file_threshold: 0,
+            content_length: None,
+            range: None,
+            precompressed: None,
+            charset: None,
+            content_language: None,
+            max_ranges: DEFAULT_MAX_RANGES,
+            change_check_interval: DEFAULT_CHANGE_CHECK_INTERVAL,
+            mount_path: None,
+            digest: None,
+            body_map: None,
+            transformed_length: None,
             status_code: StatusCode::OK,
             flags: Flags::default(),
         })
@@ -172,6 +389,149 @@ impl NamedFile {
         Self::from_file(File::open(&path)?, path)
     }
 
+    /// Attempts to open a file in read-only mode with the given options.
+    ///
+    /// This is the same as calling the methods corresponding to each option on the file returned by
+    /// [`NamedFile::open`].
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::{NamedFile, NamedFileOptions};
+    ///
+    /// let options = NamedFileOptions::new().prefer_utf8(true);
+    /// let file = NamedFile::open_with("foo.txt", &options);
+    /// ```
+    pub fn open_with<P: AsRef<Path>>(
+        path: P,
+        options: &NamedFileOptions,
+    ) -> io::Result<NamedFile> {
+        let mut named_file = Self::open(path)?;
+        named_file.flags = options.flags;
+        named_file.disposition_policy = options.disposition_policy;
+        named_file.cache_control = options.cache_control.clone();
+        Ok(named_file)
+    }
+
+    /// Attempts to open a file in read-only mode, using the MIME type from `overrides` for its
+    /// extension if there is one.
+    ///
+    /// The `Content-Disposition` type follows the overridden MIME type in the same way as it
+    /// follows a guessed one: `inline` for text, image, and video content types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use actix_files::{MimeOverride, NamedFile};
+    ///
+    /// let overrides = MimeOverride::new().add("wasm", "application/wasm".parse().unwrap());
+    /// let file = NamedFile::open_with_mime_override("app.wasm", &overrides);
+    /// ```
+    pub fn open_with_mime_override<P: AsRef<Path>>(
+        path: P,
+        overrides: &MimeOverride,
+    ) -> io::Result<NamedFile> {
+        let mut named_file = Self::open(&path)?;
+
+        if let Some(ct) = overrides.get_for_path(path.as_ref()) {
+            named_file.content_disposition.disposition = default_disposition(ct);
+            named_file.content_type = ct.clone();
+        }
+
+        Ok(named_file)
+    }
+
+    /// Attempts to open a file in read-only mode without blocking the current thread.
+    ///
+    /// Opening the file and reading its metadata is performed on the blocking thread-pool, so
+    /// this is preferable to [`NamedFile::open`] when called from within a request handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use actix_files::NamedFile;
+    ///
+    /// # async fn open() {
+    /// let file = NamedFile::open_async("foo.txt").await;
+    /// # }
+    /// ```
+    pub async fn open_async<P: AsRef<Path>>(path: P) -> io::Result<NamedFile> {
+        let fs_path = path.as_ref().to_path_buf();
+
+        let (file, md) = web::block(move || {
+            let file = File::open(fs_path)?;
+            let md = file.metadata()?;
+            Ok::<_, io::Error>((file, md))
+        })
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))??;
+
+        Self::from_file_with_metadata(file, path, md)
+    }
+
+    /// Attempts to open a file, or a precompressed variant of it accepted by the request.
+    ///
+    /// For each content encoding accepted by the request's `Accept-Encoding` header, a sibling file
+    /// named after `path` with a `.br` (Brotli), `.zst` (Zstandard) or `.gz` (gzip) extension
+    /// appended is looked for.
+    /// The first one found is served with the matching `Content-Encoding` header; its metadata is
+    /// used for the ETag and `Last-Modified` headers. The `Content-Type` and `Content-Disposition`
+    /// headers are still derived from `path`. If no acceptable variant exists, the file at `path`
+    /// is opened as with [`NamedFile::open`].
+    ///
+    /// Responses include a `Vary: Accept-Encoding` header either way.
+    ///
+    /// # Compression
+    /// Precompressed variants are marked as already encoded, so the
+    /// [`Compress`](actix_web::middleware::Compress) middleware sends them unchanged instead of
+    /// compressing them again. When the file at `path` is served instead, the middleware may
+    /// compress it as usual.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::{HttpRequest, Responder};
+    ///
+    /// async fn app_js(req: HttpRequest) -> impl Responder {
+    ///     NamedFile::open_with_precompressed("./static/app.js", &req)
+    /// }
+    /// ```
+    pub fn open_with_precompressed<P: AsRef<Path>>(
+        path: P,
+        req: &HttpRequest,
+    ) -> io::Result<NamedFile> {
+        Self::open_precompressed(path.as_ref(), req.headers())
+    }
+
+    /// Opens `path` or a precompressed variant of it acceptable to a request with `headers`.
+    pub(crate) fn open_precompressed(
+        path: &Path,
+        headers: &HeaderMap,
+    ) -> io::Result<NamedFile> {
+        for (encoding, ext) in precompressed_variants(headers) {
+            let mut variant_path = path.as_os_str().to_owned();
+            variant_path.push(".");
+            variant_path.push(ext);
+
+            let file = match File::open(&variant_path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            let md = match file.metadata() {
+                Ok(md) if md.is_file() => md,
+                _ => continue,
+            };
+
+            let mut named_file = Self::from_file_with_metadata(file, path, md)?;
+            named_file.precompressed = Some(encoding);
+            return Ok(named_file);
+        }
+
+        let mut named_file = Self::open(path)?;
+        named_file.precompressed = Some(ContentEncoding::Identity);
+        Ok(named_file)
+    }
+
     /// Returns reference to the underlying `File` object.
     #[inline]
     pub fn file(&self) -> &File {
@@ -197,7 +557,41 @@ impl NamedFile {
         self.path.as_path()
     }
 
+    /// Returns the `Content-Type` that will be sent with this file.
+    ///
+    /// This is the type guessed from the file name unless it was replaced with
+    /// [`set_content_type`](Self::set_content_type).
+    #[inline]
+    pub fn content_type(&self) -> &mime::Mime {
+        &self.content_type
+    }
+
+    /// Returns the `Content-Disposition` that will be sent with this file.
+    #[inline]
+    pub fn content_disposition(&self) -> &header::ContentDisposition {
+        &self.content_disposition
+    }
+
+    /// Returns the metadata read from the file when it was opened.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        &self.md
+    }
+
+    /// Returns the modification time of this file, if known.
+    ///
+    /// This is the time set with [`set_modified`](Self::set_modified), if any, or otherwise the
+    /// one read from the file's metadata.
+    #[inline]
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
     /// Set response **Status Code**
+    ///
+    /// The status code is replaced by a conditional status such as `304 Not Modified` where the
+    /// request calls for one, and successful ones also by `206 Partial Content` for ranges; see
+    /// [`into_response`](Self::into_response).
     pub fn set_status_code(mut self, status: StatusCode) -> Self {
         self.status_code = status;
         self
@@ -211,6 +605,62 @@ impl NamedFile {
         self
     }
 
+    /// Set the MIME Content-Type for serving this file and derive the disposition type from it.
+    ///
+    /// Like [`set_content_type`](Self::set_content_type), except that the `inline` or
+    /// `attachment` disposition type is chosen again for the new content type, as it is for
+    /// inferred types. A disposition set explicitly through
+    /// [`set_content_disposition`](Self::set_content_disposition),
+    /// [`set_inline`](Self::set_inline) or [`set_attachment`](Self::set_attachment) is kept.
+    #[inline]
+    pub fn set_content_type_and_disposition(mut self, mime_type: mime::Mime) -> Self {
+        if !self.flags.contains(Flags::CUSTOM_DISPOSITION) {
+            self.content_disposition.disposition = default_disposition(&mime_type);
+        }
+        self.content_type = mime_type;
+        self
+    }
+
+    /// Specifies whether the content type is detected from the first bytes of the file.
+    ///
+    /// When enabled, the start of the file is checked against the signatures of common binary
+    /// formats, such as PNG images or PDF documents. This helps with files whose names are chosen
+    /// by clients, like uploads. At most 512 bytes are read, at once and without affecting the
+    /// body. A recognized type only replaces the one guessed from the extension if the guess is
+    /// `application/octet-stream` or has another top-level type, and archive types never replace a
+    /// guess, so that e.g. `.docx` files are not served as ZIP archives. Other files keep their
+    /// type. The disposition type is derived from a detected type as in
+    /// [`set_content_type_and_disposition`](Self::set_content_type_and_disposition).
+    ///
+    /// Detection happens when this is called, so a content type set afterwards takes precedence.
+    /// Default is false.
+    pub fn detect_content_type_from_content(self, value: bool) -> Self {
+        if !value {
+            return self;
+        }
+
+        match chunked::read_chunk(&self.file, 0, SNIFF_LEN) {
+            Ok(prefix) => match sniff::detect(&self.content_type, &prefix) {
+                Some(content_type) => self.set_content_type_and_disposition(content_type),
+                None => self,
+            },
+            Err(err) => {
+                log::debug!("failed to read {:?}: {}", self.path, err);
+                self
+            }
+        }
+    }
+
+    /// Set the modification time advertised for this file.
+    ///
+    /// This overrides the time read from the file's metadata and is used for both the
+    /// `Last-Modified` header and the generated ETag.
+    #[inline]
+    pub fn set_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
     /// Set the Content-Disposition for serving this file. This allows
     /// changing the inline/attachment disposition as well as the filename
     /// sent to the peer. By default the disposition is `inline` for text,
@@ -218,9 +668,63 @@ impl NamedFile {
     /// the filename is taken from the path provided in the `open` method
     /// after converting it to UTF-8 using.
     /// [`std::ffi::OsStr::to_string_lossy`]
+    ///
+    /// The default `filename` parameter is an ASCII-only fallback; names that had to be altered
+    /// are also sent unmodified in the `filename*` parameter. Setting a disposition here replaces
+    /// both.
     #[inline]
     pub fn set_content_disposition(mut self, cd: header::ContentDisposition) -> Self {
         self.content_disposition = cd;
+        self.flags
+            .insert(Flags::CONTENT_DISPOSITION | Flags::CUSTOM_DISPOSITION);
+        self
+    }
+
+    /// Set the `Content-Disposition` type to `inline`, keeping its parameters.
+    #[inline]
+    pub fn set_inline(mut self) -> Self {
+        self.content_disposition.disposition = DispositionType::Inline;
+        self.flags
+            .insert(Flags::CONTENT_DISPOSITION | Flags::CUSTOM_DISPOSITION);
+        self
+    }
+
+    /// Set the `Content-Disposition` type to `attachment`, prompting browsers to download the
+    /// file.
+    ///
+    /// If `filename` is given, it replaces the filename parameters; other parameters are kept.
+    #[inline]
+    pub fn set_attachment(mut self, filename: Option<String>) -> Self {
+        let cd = &mut self.content_disposition;
+        cd.disposition = DispositionType::Attachment;
+
+        if let Some(filename) = filename {
+            set_filename(cd, &filename);
+        }
+
+        self.flags
+            .insert(Flags::CONTENT_DISPOSITION | Flags::CUSTOM_DISPOSITION);
+        self
+    }
+
+    /// Set the filename sent in the `Content-Disposition` header, keeping its type.
+    ///
+    /// The inferred `inline` or `attachment` type and other parameters are left as they are, while
+    /// the filename parameters are replaced as in [`set_attachment`](Self::set_attachment). This
+    /// also re-enables the header if it was disabled.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("tmp/abc123")?.set_download_filename("report-2024.pdf");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn set_download_filename(mut self, filename: &str) -> Self {
+        set_filename(&mut self.content_disposition, filename);
         self.flags.insert(Flags::CONTENT_DISPOSITION);
         self
     }
@@ -229,32 +733,628 @@ impl NamedFile {
     ///
     /// By default Content-Disposition` header is enabled.
     #[inline]
-    pub fn disable_content_disposition(mut self) -> Self {
-        self.flags.remove(Flags::CONTENT_DISPOSITION);
+    pub fn disable_content_disposition(mut self) -> Self {
+        self.flags.remove(Flags::CONTENT_DISPOSITION);
+        self
+    }
+
+    /// Set when the `Content-Disposition` header is sent.
+    ///
+    /// This allows leaving the header off for files shown inline while keeping it for downloads.
+    /// It applies on top of [`disable_content_disposition`](Self::disable_content_disposition),
+    /// which turns the header off regardless of the policy. Default is
+    /// [`DispositionPolicy::Always`].
+    #[inline]
+    pub fn set_disposition_policy(mut self, policy: DispositionPolicy) -> Self {
+        self.disposition_policy = policy;
+        self
+    }
+
+    /// Returns true if the `Content-Disposition` header is to be sent.
+    fn use_content_disposition(&self) -> bool {
+        if !self.flags.contains(Flags::CONTENT_DISPOSITION) {
+            return false;
+        }
+
+        match self.disposition_policy {
+            DispositionPolicy::Always => true,
+            DispositionPolicy::NeverForInline => !self.content_disposition.is_inline(),
+            DispositionPolicy::Never => false,
+        }
+    }
+
+    /// Set the `Content-Language` header for serving this file.
+    ///
+    /// Responses will also carry `Vary: Accept-Language`, since a localized file is typically
+    /// one of several chosen per request.
+    #[inline]
+    pub fn set_content_language(mut self, language: header::LanguageTag) -> Self {
+        self.content_language = Some(language);
+        self
+    }
+
+    /// Set content encoding for serving this file
+    ///
+    /// Must be used with [`actix_web::middleware::Compress`] to take effect.
+    #[inline]
+    pub fn set_content_encoding(mut self, enc: ContentEncoding) -> Self {
+        self.encoding = Some(enc);
+        self
+    }
+
+    /// Set the length of the content served in place of the file's size.
+    ///
+    /// This is the length advertised in `Content-Length` and `Content-Range` headers and used to
+    /// resolve ranges, and the number of bytes read from the file. It is meant for files whose
+    /// size on disk is known to differ from what should be sent, such as ones being appended to.
+    ///
+    /// The length is not checked against the file. Responses for a larger length end early, which
+    /// clients see as a truncated response; a smaller length cuts the file short.
+    #[inline]
+    pub fn set_content_length(mut self, length: u64) -> Self {
+        self.content_length = Some(length);
+        self
+    }
+
+    /// Set the `Cache-Control` directives sent when serving this file.
+    ///
+    /// The header is included in full, partial, and `304 Not Modified` responses.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::http::header::{CacheControl, CacheDirective};
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("Cargo.toml")?
+    ///     .set_cache_control(CacheControl(vec![CacheDirective::Public, CacheDirective::NoCache]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn set_cache_control(mut self, cache_control: header::CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Send a `Repr-Digest` header with a hash of the file's contents, computed with `algo`.
+    ///
+    /// Files are hashed on the blocking thread-pool, and responses are sent without the header until
+    /// the digest is ready. Each worker thread keeps the digests of recently served files, by path,
+    /// and only computes them again once a file's size or modification time changes. Note that the
+    /// digest no longer matches if the response is compressed by the
+    /// [`Compress`](actix_web::middleware::Compress) middleware.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::{DigestAlgo, NamedFile};
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("./dist/app.tar.gz")?.with_digest(DigestAlgo::Sha256);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_digest(mut self, algo: DigestAlgo) -> Self {
+        self.digest = Some(DigestMode::Always(algo));
+        self
+    }
+
+    /// Send a `Repr-Digest` header to clients asking for one, with the hash algorithm they prefer.
+    ///
+    /// Like [`with_digest`](Self::with_digest), except that the file is only hashed for requests
+    /// with a `Want-Repr-Digest` header, or the older `Want-Digest` header, that accepts one of the
+    /// supported [algorithms](DigestAlgo). Among those, the one the client prefers is used. Other
+    /// requests are served without a digest and without the cost of computing it. Since the
+    /// response depends on these headers, they are listed in `Vary`.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// // requests with `Want-Repr-Digest: sha-256=3, sha-512=8` are sent a SHA-512 digest
+    /// let file = NamedFile::open("./dist/app.tar.gz")?.with_requested_digest();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_requested_digest(mut self) -> Self {
+        self.digest = Some(DigestMode::Requested);
+        self
+    }
+
+    /// Set the `max-age` cache directive sent when serving this file.
+    ///
+    /// Any previously set `max-age` directive is replaced while other directives are kept. Durations
+    /// longer than `u32::MAX` seconds are truncated.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("Cargo.toml")?.set_max_age(Duration::from_secs(3600));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_max_age(mut self, max_age: Duration) -> Self {
+        let secs = cmp::min(max_age.as_secs(), u64::from(u32::MAX)) as u32;
+
+        let cc = self
+            .cache_control
+            .get_or_insert_with(|| header::CacheControl(Vec::new()));
+        cc.0.retain(|directive| !matches!(directive, header::CacheDirective::MaxAge(_)));
+        cc.0.push(header::CacheDirective::MaxAge(secs));
+
+        self
+    }
+
+    /// Set an `Expires` header relative to the time each response is generated.
+    ///
+    /// This complements [`set_max_age`](Self::set_max_age) for HTTP/1.0 caches that do not
+    /// understand `Cache-Control`. Durations longer than a year are shortened to one year.
+    #[inline]
+    pub fn set_expires(mut self, ttl: Duration) -> Self {
+        self.expires = Some(ttl);
+        self
+    }
+
+    /// Set the size of the buffer used for each chunk read from the file while streaming.
+    ///
+    /// Larger buffers reduce the number of reads needed to serve large files at the expense of
+    /// memory held by each in-flight response. Default is 64KiB.
+    ///
+    /// # Panics
+    /// Panics if `buffer_size` is 0.
+    #[inline]
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        assert_ne!(buffer_size, 0, "buffer size must be greater than 0");
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Set the maximum number of chunks read from the file at once while it is sent.
+    ///
+    /// By default a chunk is only read once the previous one was sent, which can leave the
+    /// connection idle while waiting on each read. With read-ahead, up to `chunks` reads are run
+    /// concurrently on the blocking thread-pool and buffered, using at most `chunks` times the
+    /// [buffer size](Self::with_buffer_size) of memory per response. Values of 0 and 1 both read
+    /// one chunk at a time. Default is 0.
+    #[inline]
+    pub fn read_ahead(mut self, chunks: usize) -> Self {
+        self.read_ahead = chunks;
+        self
+    }
+
+    /// Set the size below which files are read into memory and sent as a single body.
+    ///
+    /// Streaming has some overhead per response that is not worth paying for tiny files such as
+    /// icons. Files smaller than `bytes` are instead read in full when the response is built, on
+    /// the calling thread, so the threshold should stay small. Ranges are still honored by only
+    /// reading the requested part. Files with an [`on_bytes_sent`](Self::on_bytes_sent) or
+    /// [`map_body`](Self::map_body) function are always streamed. Default is 0, meaning all files
+    /// are streamed.
+    #[inline]
+    pub fn small_file_threshold(mut self, bytes: usize) -> Self {
+        self.small_file_threshold = bytes as u64;
+        self
+    }
+
+    /// Set the maximum number of ranges accepted in a single `Range` header.
+    ///
+    /// Requests asking for more ranges, counted once overlapping and adjacent ones are
+    /// [merged](Self::coalesce_ranges), are answered with `416 Range Not Satisfiable`. Without a
+    /// limit, a header listing thousands of tiny ranges makes the server do a disproportionate amount
+    /// of work for a single request. Default is 10.
+    #[inline]
+    pub fn max_ranges(mut self, max_ranges: usize) -> Self {
+        self.max_ranges = max_ranges;
+        self
+    }
+
+    /// Specifies whether overlapping and adjacent ranges in a `Range` header are merged before the
+    /// response is built.
+    ///
+    /// Of several ranges, only the first one requested is served, together with the ranges merged
+    /// into it. Default is true.
+    #[inline]
+    pub fn coalesce_ranges(mut self, value: bool) -> Self {
+        self.flags.set(Flags::COALESCE_RANGES, value);
+        self
+    }
+
+    /// Specifies whether an unsatisfiable `Range` header is ignored.
+    ///
+    /// When enabled, requests whose ranges cannot be satisfied (or that exceed
+    /// [`max_ranges`](Self::max_ranges)) are served the whole file with `200 OK` instead of
+    /// `416 Range Not Satisfiable`, as permitted by RFC 7233. Default is false.
+    #[inline]
+    pub fn ignore_unsatisfiable_range(mut self, value: bool) -> Self {
+        self.flags.set(Flags::IGNORE_BAD_RANGE, value);
+        self
+    }
+
+    /// Specifies whether range requests are supported.
+    ///
+    /// When disabled, the `Accept-Ranges` header is not sent and any `Range` header is ignored, so
+    /// the whole file is always served. Default is true.
+    #[inline]
+    pub fn accept_ranges(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ACCEPT_RANGES, value);
+        self
+    }
+
+    /// Specifies whether the `Accept-Ranges: bytes` header is sent.
+    ///
+    /// This only affects the header; whether `Range` headers are honored is set with
+    /// [`accept_ranges`](Self::accept_ranges). Default is true.
+    #[inline]
+    pub fn advertise_ranges(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ADVERTISE_RANGES, value);
+        self
+    }
+
+    /// Specifies whether `Range` headers are honored for precompressed files.
+    ///
+    /// Ranges of a [precompressed](Self::open_with_precompressed) variant select bytes of the
+    /// encoded content, which clients expecting ranges of the decoded file would misinterpret.
+    /// Such files are therefore sent whole with `Accept-Ranges: none` unless this is enabled.
+    /// Files with a [content encoding](Self::set_content_encoding) for the `Compress` middleware
+    /// are not affected, since partial responses are sent without compression. Default is false.
+    #[inline]
+    pub fn encoded_ranges(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ENCODED_RANGES, value);
+        self
+    }
+
+    /// Specifies whether to use ETag or not.
+    ///
+    /// Default is true.
+    #[inline]
+    pub fn use_etag(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ETAG, value);
+        self
+    }
+
+    /// Specifies whether to leave the inode number out of generated ETags.
+    ///
+    /// By default, ETags on Unix platforms include the file's inode number. Replicas serving the
+    /// same file from separate disks will generate different ETags for it, defeating client caches
+    /// behind a load balancer. Excluding the inode makes ETags depend only on the file size and
+    /// modification time, trading per-inode uniqueness for stability across hosts.
+    ///
+    /// Default is false.
+    #[inline]
+    pub fn use_etag_without_inode(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ETAG_NO_INODE, value);
+        self
+    }
+
+    /// Specifies whether generated ETags are weak validators.
+    ///
+    /// Weak ETags still allow `If-None-Match` revalidation but, because `If-Match` and `If-Range`
+    /// require strong comparison, requests using them with a specific tag will never match. In
+    /// particular, clients resuming a download with `If-Range` and the ETag are sent the whole
+    /// file with `200 OK`; an `If-Range` date is still honored.
+    ///
+    /// Default is false.
+    #[inline]
+    pub fn use_weak_etag(mut self, value: bool) -> Self {
+        self.flags.set(Flags::WEAK_ETAG, value);
+        self
+    }
+
+    /// Sets a function used to generate ETags instead of the built-in format.
+    ///
+    /// The function receives the file's metadata and path, allowing ETags to be derived from a hash
+    /// of the file contents or read from a sidecar file. Returning `None` omits the ETag.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::http::header::EntityTag;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("Cargo.toml")?.set_etag_fn(|md, _path| {
+    ///     Some(EntityTag::strong(format!("len-{}", md.len())))
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_etag_fn<F>(mut self, etag_fn: F) -> Self
+    where
+        F: Fn(&Metadata, &Path) -> Option<header::EntityTag> + 'static,
+    {
+        self.etag_fn = Some(Rc::new(etag_fn));
+        self
+    }
+
+    /// Sets the function used to build the response when the file cannot be opened while being
+    /// served as a service.
+    ///
+    /// This only applies when the `NamedFile` is registered with [`App::service`], which opens the
+    /// file again for each request. By default, the response is `404 Not Found` if the file is
+    /// missing, `403 Forbidden` if access is denied, and `500 Internal Server Error` otherwise.
+    ///
+    /// [`App::service`]: actix_web::App::service
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::{App, HttpResponse};
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let app = App::new().service(NamedFile::open("./static/index.html")?.on_open_error(
+    ///     |_err, _req| HttpResponse::NotFound().body("<h1>Nothing here</h1>"),
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_open_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(io::Error, &HttpRequest) -> HttpResponse + 'static,
+    {
+        self.open_error_handler = Some(Rc::new(handler));
+        self
+    }
+
+    /// Set the URL path the file is served at when registered as a service.
+    ///
+    /// By default the file is served at any path starting with the path it was opened with. A
+    /// mount path is instead matched exactly, relative to the enclosing scope, and may contain
+    /// [dynamic segments](actix_web::Resource::new).
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_web::App;
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let app = App::new().service(NamedFile::open("./dist/index.html")?.at("/"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn at(mut self, mount_path: &str) -> Self {
+        self.mount_path = Some(mount_path.to_owned());
+        self
+    }
+
+    /// Sets the request methods answered while being served as a service.
+    ///
+    /// Requests with other methods get a `405 Method Not Allowed` response listing the allowed
+    /// methods in its `Allow` header. If `OPTIONS` is allowed, such requests are answered with
+    /// `204 No Content` and the same `Allow` header instead of the file. Default is `GET`, `HEAD`
+    /// and `OPTIONS`.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::{http::Method, App};
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let app = App::new()
+    ///     .service(NamedFile::open("./static/index.html")?.allowed_methods(&[Method::GET]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn allowed_methods(mut self, methods: &[Method]) -> Self {
+        self.allowed_methods = Some(methods.to_vec());
+        self
+    }
+
+    /// Specifies whether the file is kept open and reused across requests while being served as a
+    /// service.
+    ///
+    /// By default, a `NamedFile` registered with [`App::service`] opens its path again for every
+    /// request, so changes on disk are picked up straight away. Keeping the file open saves the
+    /// `open` and `stat` calls each time, at the cost of serving the originally opened file even
+    /// if it is replaced or removed; see [`revalidate_after`](Self::revalidate_after) for
+    /// picking up such changes periodically. Default is false.
+    ///
+    /// [`App::service`]: actix_web::App::service
+    #[inline]
+    pub fn keep_open(mut self, value: bool) -> Self {
+        self.flags.set(Flags::KEEP_OPEN, value);
+        self
+    }
+
+    /// Specifies whether the response body ends with an error if the file changes while it is
+    /// being sent.
+    ///
+    /// The file's metadata is read again before each chunk, at most once per
+    /// [`change_check_interval`](Self::change_check_interval). If its size or modification time
+    /// differ from when it was opened, or (on Unix) another file was renamed over it, the body
+    /// stream fails and the connection is closed, so clients retry instead of keeping a mix of
+    /// old and new bytes. Memory-mapped files (see `use_mmap`) are not checked. Default is false.
+    #[inline]
+    pub fn abort_on_change(mut self, value: bool) -> Self {
+        self.flags.set(Flags::ABORT_ON_CHANGE, value);
+        self
+    }
+
+    /// Sets the minimum time between checks for changes enabled with
+    /// [`abort_on_change`](Self::abort_on_change). Default is 1 second.
+    #[inline]
+    pub fn change_check_interval(mut self, interval: Duration) -> Self {
+        self.change_check_interval = interval;
+        self
+    }
+
+    /// Returns the check for changes while the file is read, if enabled.
+    fn change_check(&self) -> Option<ChangeCheck> {
+        if self.flags.contains(Flags::ABORT_ON_CHANGE) {
+            Some(ChangeCheck::new(
+                self.md.clone(),
+                self.change_check_interval,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Sets how often a file [kept open](Self::keep_open) is checked against its path.
+    ///
+    /// On the first request after `interval` has passed since the last check, the path's metadata
+    /// is read again. If the file was replaced or modified, it is reopened with the same settings;
+    /// if it was removed, the request is answered as if the file could not be opened. By default the
+    /// file is never checked.
+    #[inline]
+    pub fn revalidate_after(mut self, interval: Duration) -> Self {
+        self.revalidate_after = Some(interval);
+        self
+    }
+
+    /// Sets a function to observe how much of the file has been sent.
+    ///
+    /// While the response body is streamed, it is called with the total number of bytes read from
+    /// the file so far after each chunk. It is called once more when the body is dropped, whether
+    /// it was sent in full or the download was aborted, so the last value reported is the total
+    /// delivered. It is not called for responses without a body, such as to `HEAD` requests.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::{cell::Cell, rc::Rc};
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let sent = Rc::new(Cell::new(0));
+    /// let sent2 = Rc::clone(&sent);
+    /// let file = NamedFile::open("Cargo.toml")?.on_bytes_sent(move |n| sent2.set(n));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_bytes_sent<F>(mut self, bytes_sent_fn: F) -> Self
+    where
+        F: Fn(u64) + 'static,
+    {
+        self.bytes_sent_fn = Some(Rc::new(bytes_sent_fn));
+        self
+    }
+
+    /// Sets a function to transform each chunk of the body as the file is sent.
+    ///
+    /// This allows light edits, such as stripping a byte order mark, without reading the whole
+    /// file into memory first. Since the size of the transformed body is not known up front, it
+    /// is sent without a `Content-Length` header unless given with
+    /// [`set_transformed_length`](Self::set_transformed_length). Range requests are refused with
+    /// `Accept-Ranges: none` and a [digest](Self::with_digest) is not sent either. The `ETag` and
+    /// `Last-Modified` headers are still derived from the file, so a transform whose output
+    /// changes without the file changing should be paired with [`use_etag(false)`](Self::use_etag)
+    /// and [`use_last_modified(false)`](Self::use_last_modified).
+    ///
+    /// Chunks are split at arbitrary offsets, so the function must not expect a pattern it
+    /// replaces to arrive in a single chunk.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use bytes::Bytes;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let file = NamedFile::open("Cargo.toml")?
+    ///     .map_body(|chunk: Bytes| Bytes::from(chunk.to_ascii_uppercase()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn map_body<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Bytes) -> Bytes + 'static,
+    {
+        self.body_map = Some(Rc::new(f));
+        self
+    }
+
+    /// Sets a function to transform each chunk read from the file, such as to decrypt it.
+    ///
+    /// This is [`map_body`](Self::map_body) for functions working on byte slices, and has the
+    /// same constraints: ranges are refused, and the response has no `Content-Length` unless the
+    /// size of the output is given with [`set_transformed_length`](Self::set_transformed_length).
+    /// Every chunk but the last holds exactly the [buffer size](Self::with_buffer_size) of bytes,
+    /// so for formats encrypted in fixed-size blocks or records, the buffer size should be a
+    /// multiple of the record size to keep each record within one chunk.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    ///
+    /// # fn run() -> std::io::Result<()> {
+    /// let key = 0x5a;
+    /// let file = NamedFile::open("secret.bin")?
+    ///     .with_reader_transform(move |chunk| chunk.iter().map(|b| b ^ key).collect());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_reader_transform<F>(self, f: F) -> Self
+    where
+        F: Fn(&[u8]) -> Vec<u8> + 'static,
+    {
+        self.map_body(move |chunk| Bytes::from(f(&chunk)))
+    }
+
+    /// Set the length of the body produced by the [body transform](Self::map_body).
+    ///
+    /// When the size of the transformed content is known, such as the plaintext size of an
+    /// encrypted file, it is sent as the `Content-Length`. It is not checked; a response whose
+    /// body is a different size is cut short or ends early. Without a transform this has no
+    /// effect.
+    #[inline]
+    pub fn set_transformed_length(mut self, length: u64) -> Self {
+        self.transformed_length = Some(length);
+        self
+    }
+
+    /// Specifies whether the file is served from a memory map instead of being read in chunks.
+    ///
+    /// Mapping avoids a read call per chunk, which can help with large, frequently requested files.
+    /// If the file cannot be mapped, it is read as usual. Default is false.
+    ///
+    /// # Truncation
+    /// If a mapped file is truncated while being served, accessing the missing pages raises
+    /// `SIGBUS` on Unix, which terminates the server. Only enable this for files that are never
+    /// modified in place, such as a directory of versioned build assets.
+    #[cfg(feature = "mmap")]
+    #[inline]
+    pub fn use_mmap(mut self, value: bool) -> Self {
+        self.flags.set(Flags::USE_MMAP, value);
+        self
+    }
+
+    /// Specifies whether to set the `Date` header on responses.
+    ///
+    /// The date is taken when the response is built, alongside `Last-Modified`. Default is true.
+    #[inline]
+    pub fn use_date_header(mut self, value: bool) -> Self {
+        self.flags.set(Flags::DATE, value);
         self
     }
 
-    /// Set content encoding for serving this file
+    /// Specifies whether conditional request headers are evaluated for files served with a status
+    /// code other than `2xx`.
     ///
-    /// Must be used with [`actix_web::middleware::Compress`] to take effect.
+    /// When enabled, such responses carry the `ETag` and `Last-Modified` validators and can be
+    /// answered with `304 Not Modified` or `412 Precondition Failed`. Disable this to always send
+    /// the file with the status code set by [`set_status_code`](Self::set_status_code). Default is
+    /// true.
     #[inline]
-    pub fn set_content_encoding(mut self, enc: ContentEncoding) -> Self {
-        self.encoding = Some(enc);
+    pub fn conditional_custom_status(mut self, value: bool) -> Self {
+        self.flags.set(Flags::CONDITIONAL_CUSTOM, value);
         self
     }
 
-    /// Specifies whether to use ETag or not.
+    /// Specifies whether empty files are served with `204 No Content` instead of `200 OK`.
     ///
-    /// Default is true.
+    /// Such responses have no body and no `Content-Length` header. Other status codes set with
+    /// [`set_status_code`](Self::set_status_code) are kept. Default is false.
     #[inline]
-    pub fn use_etag(mut self, value: bool) -> Self {
-        self.flags.set(Flags::ETAG, value);
+    pub fn empty_as_no_content(mut self, value: bool) -> Self {
+        self.flags.set(Flags::EMPTY_NO_CONTENT, value);
         self
     }
 
     /// Specifies whether to use Last-Modified or not.
     ///
-    /// Default is true.
+    /// The header has second precision, so a file changed twice within one second looks
+    /// unchanged to clients revalidating with `If-Modified-Since` alone. Default is true.
     #[inline]
     pub fn use_last_modified(mut self, value: bool) -> Self {
         self.flags.set(Flags::LAST_MD, value);
@@ -270,9 +1370,38 @@ impl NamedFile {
         self
     }
 
+    /// Set the charset signalled for text responses in place of UTF-8.
+    ///
+    /// This implies [`prefer_utf8(true)`](Self::prefer_utf8); the charset is only added to text
+    /// content types that do not already carry parameters.
+    #[inline]
+    pub fn set_content_type_charset(mut self, charset: header::Charset) -> Self {
+        self.flags.insert(Flags::PREFER_UTF8);
+        self.charset = Some(charset);
+        self
+    }
+
+    /// Returns the content type to send, with the configured charset if enabled.
+    fn response_content_type(&self) -> mime::Mime {
+        if !self.flags.contains(Flags::PREFER_UTF8) {
+            return self.content_type.clone();
+        }
+
+        match self.charset {
+            Some(ref charset) => with_charset(self.content_type.clone(), &charset.to_string()),
+            None => with_charset(self.content_type.clone(), "utf-8"),
+        }
+    }
+
     pub(crate) fn etag(&self) -> Option<header::EntityTag> {
+        if let Some(ref etag_fn) = self.etag_fn {
+            return etag_fn(&self.md, &self.path);
+        }
+
         // This etag format is similar to Apache's.
-        self.modified.as_ref().map(|mtime| {
+        //
+        // No etag is generated for files modified before the Unix epoch.
+        self.modified.as_ref().and_then(|mtime| {
             let ino = {
                 #[cfg(unix)]
                 {
@@ -284,17 +1413,30 @@ impl NamedFile {
                 }
             };
 
-            let dur = mtime
-                .duration_since(UNIX_EPOCH)
-                .expect("modification time must be after epoch");
+            let dur = mtime.duration_since(UNIX_EPOCH).ok()?;
 
-            header::EntityTag::strong(format!(
-                "{:x}:{:x}:{:x}:{:x}",
-                ino,
-                self.md.len(),
-                dur.as_secs(),
-                dur.subsec_nanos()
-            ))
+            let tag = if self.flags.contains(Flags::ETAG_NO_INODE) {
+                format!(
+                    "{:x}:{:x}:{:x}",
+                    self.md.len(),
+                    dur.as_secs(),
+                    dur.subsec_nanos()
+                )
+            } else {
+                format!(
+                    "{:x}:{:x}:{:x}:{:x}",
+                    ino,
+                    self.md.len(),
+                    dur.as_secs(),
+                    dur.subsec_nanos()
+                )
+            };
+
+            if self.flags.contains(Flags::WEAK_ETAG) {
+                Some(header::EntityTag::weak(tag))
+            } else {
+                Some(header::EntityTag::strong(tag))
+            }
         })
     }
 
@@ -302,34 +1444,81 @@ impl NamedFile {
         self.modified.map(|mtime| mtime.into())
     }
 
+    /// Creates a `206 Partial Content` response for `length` bytes of the file from `start`.
+    ///
+    /// The range is served in place of any requested with a `Range` header, regardless of
+    /// [`accept_ranges`](Self::accept_ranges) and `If-Range`. Ranges that are empty or do not fit
+    /// within the file are answered with `416 Range Not Satisfiable`. Conditional request headers
+    /// are handled as with [`into_response`](Self::into_response).
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::NamedFile;
+    /// use actix_web::{HttpRequest, HttpResponse};
+    ///
+    /// async fn segment(req: HttpRequest) -> std::io::Result<HttpResponse> {
+    ///     let file = NamedFile::open("./media/stream.ts")?;
+    ///     Ok(file.into_response_for_range(&req, 188 * 1024, 188 * 512))
+    /// }
+    /// ```
+    pub fn into_response_for_range(
+        mut self,
+        req: &HttpRequest,
+        start: u64,
+        length: u64,
+    ) -> HttpResponse {
+        self.range = Some(HttpRange { start, length });
+        self.into_response(req)
+    }
+
     /// Creates an `HttpResponse` with file as a streaming body.
+    ///
+    /// Ranges are only handled for successful (`2xx`) status codes; files served with other status
+    /// codes, such as error pages, are always sent whole. Conditional requests are handled for any
+    /// status code unless disabled with
+    /// [`conditional_custom_status`](Self::conditional_custom_status).
+    ///
+    /// HTTP dates only have second precision, so `Last-Modified` is sent rounded down to whole
+    /// seconds and `If-Modified-Since` and `If-Unmodified-Since` are compared at whole seconds.
+    /// The default `ETag` includes the sub-second part of the modification time, which is why an
+    /// `If-None-Match` header takes precedence over `If-Modified-Since` when both are sent.
+    ///
+    /// Errors while preparing the body are answered with `500 Internal Server Error`; see
+    /// [`try_into_response`](Self::try_into_response) to handle them instead.
     pub fn into_response(self, req: &HttpRequest) -> HttpResponse {
-        if self.status_code != StatusCode::OK {
-            let mut res = HttpResponse::build(self.status_code);
-
-            if self.flags.contains(Flags::PREFER_UTF8) {
-                let ct = equiv_utf8_text(self.content_type.clone());
-                res.insert_header((header::CONTENT_TYPE, ct.to_string()));
-            } else {
-                res.insert_header((header::CONTENT_TYPE, self.content_type.to_string()));
-            }
-
-            if self.flags.contains(Flags::CONTENT_DISPOSITION) {
-                res.insert_header((
-                    header::CONTENT_DISPOSITION,
-                    self.content_disposition.to_string(),
-                ));
-            }
+        match self.try_into_response(req) {
+            Ok(res) => res,
+            Err(err) => HttpResponse::from_error(error::ErrorInternalServerError(err)),
+        }
+    }
 
-            if let Some(current_encoding) = self.encoding {
-                res.encoding(current_encoding);
-            }
+    /// Inserts the `Date`, `Cache-Control` and `Expires` headers sent with every response.
+    fn insert_caching_headers(&self, resp: &mut HttpResponseBuilder) {
+        // the server only adds its own Date header when the response is missing one
+        if self.flags.contains(Flags::DATE) {
+            resp.insert_header(header::Date(SystemTime::now().into()));
+        }
 
-            let reader = ChunkedReadFile::new(self.md.len(), 0, self.file);
+        if let Some(ref cache_control) = self.cache_control {
+            resp.insert_header(cache_control.clone());
+        }
 
-            return res.streaming(reader);
+        let expires = self
+            .expires
+            .and_then(|ttl| SystemTime::now().checked_add(cmp::min(ttl, MAX_EXPIRES)));
+        if let Some(expires) = expires {
+            resp.insert_header(header::Expires(expires.into()));
         }
+    }
 
+    /// Creates an `HttpResponse` with file as a streaming body, failing if the response cannot be
+    /// prepared.
+    ///
+    /// This is [`into_response`](Self::into_response) for callers that want to handle errors
+    /// themselves, such as a failure to read a [small file](Self::small_file_threshold). Errors
+    /// while streaming the body once the response has been sent cannot be reported this way and end
+    /// the body early instead.
+    pub fn try_into_response(self, req: &HttpRequest) -> Result<HttpResponse, Error> {
         let etag = if self.flags.contains(Flags::ETAG) {
             self.etag()
         } else {
@@ -342,14 +1531,15 @@ impl NamedFile {
             None
         };
 
-        // check preconditions
-        let precondition_failed = if !any_match(etag.as_ref(), req) {
-            true
+        // check preconditions; as with `If-None-Match` below, `If-Unmodified-Since` is ignored when
+        // the request has an `If-Match` header (RFC 7232 §6)
+        let precondition_failed = if req.headers().contains_key(header::IF_MATCH) {
+            !any_match(etag.as_ref(), req)
         } else if let (Some(ref m), Some(header::IfUnmodifiedSince(ref since))) =
             (last_modified, req.get_header())
         {
-            let t1: SystemTime = m.clone().into();
-            let t2: SystemTime = since.clone().into();
+            let t1: SystemTime = (*m).into();
+            let t2: SystemTime = (*since).into();
 
             match (t1.duration_since(UNIX_EPOCH), t2.duration_since(UNIX_EPOCH)) {
                 (Ok(t1), Ok(t2)) => t1.as_secs() > t2.as_secs(),
@@ -359,16 +1549,18 @@ impl NamedFile {
             false
         };
 
-        // check last modified
-        let not_modified = if !none_match(etag.as_ref(), req) {
-            true
-        } else if req.headers().contains_key(header::IF_NONE_MATCH) {
+        // check last modified; an `If-None-Match` header takes precedence over `If-Modified-Since`
+        // since entity tags can tell apart changes within the same second, which dates cannot
+        let is_get_or_head = matches!(*req.method(), Method::GET | Method::HEAD);
+        let not_modified = if req.headers().contains_key(header::IF_NONE_MATCH) {
+            !none_match(etag.as_ref(), req)
+        } else if !is_get_or_head {
             false
         } else if let (Some(ref m), Some(header::IfModifiedSince(ref since))) =
             (last_modified, req.get_header())
         {
-            let t1: SystemTime = m.clone().into();
-            let t2: SystemTime = since.clone().into();
+            let t1: SystemTime = (*m).into();
+            let t2: SystemTime = (*since).into();
 
             match (t1.duration_since(UNIX_EPOCH), t2.duration_since(UNIX_EPOCH)) {
                 (Ok(t1), Ok(t2)) => t1.as_secs() <= t2.as_secs(),
@@ -378,16 +1570,72 @@ impl NamedFile {
             false
         };
 
-        let mut resp = HttpResponse::build(self.status_code);
+        if !self.status_code.is_success() {
+            let mut res = HttpResponse::build(self.status_code);
 
-        if self.flags.contains(Flags::PREFER_UTF8) {
-            let ct = equiv_utf8_text(self.content_type.clone());
-            resp.insert_header((header::CONTENT_TYPE, ct.to_string()));
-        } else {
-            resp.insert_header((header::CONTENT_TYPE, self.content_type.to_string()));
+            res.insert_header((
+                header::CONTENT_TYPE,
+                self.response_content_type().to_string(),
+            ));
+
+            if self.use_content_disposition() {
+                res.insert_header((
+                    header::CONTENT_DISPOSITION,
+                    self.content_disposition.to_string(),
+                ));
+            }
+
+            if let Some(current_encoding) = self.encoding {
+                res.encoding(current_encoding);
+                res.append_header((header::VARY, "Accept-Encoding"));
+            }
+
+            self.insert_caching_headers(&mut res);
+
+            if self.flags.contains(Flags::CONDITIONAL_CUSTOM) {
+                if let Some(lm) = last_modified {
+                    res.insert_header((header::LAST_MODIFIED, lm.to_string()));
+                }
+
+                if let Some(ref etag) = etag {
+                    res.insert_header((header::ETAG, etag.to_string()));
+                }
+
+                if precondition_failed || (not_modified && !is_get_or_head) {
+                    return Ok(res.status(StatusCode::PRECONDITION_FAILED).finish());
+                } else if not_modified {
+                    return Ok(res.status(StatusCode::NOT_MODIFIED).finish());
+                }
+            }
+
+            let length = self.content_length.unwrap_or(self.md.len());
+            let change_check = self.change_check();
+            let reader = ChunkedReadFile::new(length, 0, self.file)
+                .buffer_size(self.buffer_size)
+                .read_ahead(self.read_ahead)
+                .bytes_sent_fn(self.bytes_sent_fn)
+                .change_check(change_check);
+
+            return Ok(send_body(
+                res,
+                length,
+                reader,
+                self.body_map,
+                self.transformed_length,
+            ));
         }
 
-        if self.flags.contains(Flags::CONTENT_DISPOSITION) {
+        // only honor the range header if the `If-Range` validator, if any, still matches
+        let range_allowed = if_range_match(etag.as_ref(), last_modified.as_ref(), req);
+
+        let mut resp = HttpResponse::build(self.status_code);
+
+        resp.insert_header((
+            header::CONTENT_TYPE,
+            self.response_content_type().to_string(),
+        ));
+
+        if self.use_content_disposition() {
             resp.insert_header((
                 header::CONTENT_DISPOSITION,
                 self.content_disposition.to_string(),
@@ -399,53 +1647,361 @@ impl NamedFile {
             resp.encoding(current_encoding);
         }
 
+        // representation depends on Accept-Encoding; appended so existing Vary fields are kept
+        if self.encoding.is_some() || self.precompressed.is_some() {
+            resp.append_header((header::VARY, "Accept-Encoding"));
+        }
+
+        if let Some(ref language) = self.content_language {
+            resp.insert_header((header::CONTENT_LANGUAGE, language.to_string()));
+            resp.append_header((header::VARY, "Accept-Language"));
+        }
+
+        if let Some(DigestMode::Requested) = self.digest {
+            resp.append_header((header::VARY, "Want-Repr-Digest, Want-Digest"));
+        }
+
+        if let Some(precompressed) = self.precompressed {
+            // content is already encoded so the compress middleware must leave it alone; files
+            // without an acceptable variant may still be compressed by it
+            if precompressed != ContentEncoding::Identity {
+                resp.encoding(ContentEncoding::Identity);
+                resp.insert_header((header::CONTENT_ENCODING, precompressed.as_str()));
+            }
+        }
+
+        self.insert_caching_headers(&mut resp);
+
         if let Some(lm) = last_modified {
             resp.insert_header((header::LAST_MODIFIED, lm.to_string()));
         }
 
-        if let Some(etag) = etag {
+        if let Some(ref etag) = etag {
             resp.insert_header((header::ETAG, etag.to_string()));
         }
 
-        resp.insert_header((header::ACCEPT_RANGES, "bytes"));
+        // only a precompressed variant is stored encoded; an encoding set for the compress
+        // middleware is not applied to partial responses
+        let is_encoded =
+            matches!(self.precompressed, Some(enc) if enc != ContentEncoding::Identity);
+        // ranges of a transformed body cannot be mapped back onto the file
+        let mapped = self.body_map.is_some();
+        let refuse_ranges =
+            mapped || (is_encoded && !self.flags.contains(Flags::ENCODED_RANGES));
+        let accept_ranges = self.flags.contains(Flags::ACCEPT_RANGES) && !refuse_ranges;
+
+        if let Some(value) = advertised_ranges(self.flags, refuse_ranges) {
+            resp.insert_header((header::ACCEPT_RANGES, value));
+        }
 
-        let mut length = self.md.len();
+        let total_length = self.content_length.unwrap_or(self.md.len());
+        let mut length = total_length;
         let mut offset = 0;
+        let mut partial = false;
+
+        // a range set through `into_response_for_range` replaces any requested one
+        let ranges = if let Some(range) = self.range.filter(|_| !mapped) {
+            let in_bounds = range.length > 0
+                && range
+                    .start
+                    .checked_add(range.length)
+                    .map_or(false, |end| end <= total_length);
+
+            Some(if in_bounds {
+                Ok(vec![range])
+            } else {
+                Err(HttpRangeError::NoOverlap)
+            })
+        } else if let Some(ranges) = req
+            .headers()
+            .get(header::RANGE)
+            .filter(|_| accept_ranges && range_allowed)
+        {
+            match ranges.to_str() {
+                Ok(ranges_header) => Some(HttpRange::parse(ranges_header, total_length)),
+                Err(_) => return Ok(resp.status(StatusCode::BAD_REQUEST).finish()),
+            }
+        } else {
+            None
+        };
+
+        let merge = self.flags.contains(Flags::COALESCE_RANGES);
+        let ranges =
+            ranges.map(|res| res.map(|ranges| range::select(ranges, merge, self.max_ranges)));
+
+        match ranges {
+            Some(Ok(Some(range))) => {
+                // suffix ranges (`bytes=-N`) are already resolved against the file length, so the
+                // start offset here is the clamped one
+                length = range.length;
+                offset = range.start;
+                partial = true;
+
+                resp.encoding(ContentEncoding::Identity);
+                resp.insert_header((
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", offset, offset + length - 1, total_length),
+                ));
+            }
 
-        // check for range header
-        if let Some(ranges) = req.headers().get(header::RANGE) {
-            if let Ok(ranges_header) = ranges.to_str() {
-                if let Ok(ranges) = HttpRange::parse(ranges_header, length) {
-                    length = ranges[0].length;
-                    offset = ranges[0].start;
+            // no range or ranges in units other than bytes, which must be ignored
+            None | Some(Err(HttpRangeError::UnsupportedUnit)) => {}
 
-                    resp.encoding(ContentEncoding::Identity);
+            Some(res) => {
+                if let Err(err) = res {
+                    log::debug!("unsatisfiable range: {}", err);
+                }
+
+                if !self.flags.contains(Flags::IGNORE_BAD_RANGE) {
+                    // always the complete length, as required for unsatisfied ranges
                     resp.insert_header((
                         header::CONTENT_RANGE,
-                        format!("bytes {}-{}/{}", offset, offset + length - 1, self.md.len()),
+                        format!("bytes */{}", total_length),
                     ));
-                } else {
-                    resp.insert_header((header::CONTENT_RANGE, format!("bytes */{}", length)));
-                    return resp.status(StatusCode::RANGE_NOT_SATISFIABLE).finish();
-                };
-            } else {
-                return resp.status(StatusCode::BAD_REQUEST).finish();
-            };
-        };
+                    return Ok(resp.status(StatusCode::RANGE_NOT_SATISFIABLE).finish());
+                }
+            }
+        }
 
-        if precondition_failed {
-            return resp.status(StatusCode::PRECONDITION_FAILED).finish();
+        // a matching `If-None-Match` only allows a cached response for `GET` and `HEAD` requests
+        if precondition_failed || (not_modified && !is_get_or_head) {
+            return Ok(resp.status(StatusCode::PRECONDITION_FAILED).finish());
         } else if not_modified {
-            return resp.status(StatusCode::NOT_MODIFIED).finish();
+            return Ok(resp.status(StatusCode::NOT_MODIFIED).finish());
         }
 
-        let reader = ChunkedReadFile::new(length, offset, self.file);
+        let digest_algo = self.digest.filter(|_| !mapped).and_then(|mode| match mode {
+            DigestMode::Always(algo) => Some(algo),
+            DigestMode::Requested => requested_digest(req),
+        });
+
+        if let Some(algo) = digest_algo {
+            let key = DigestKey {
+                path: self.path.clone(),
+                encoding: self.precompressed,
+                algo,
+                len: total_length,
+                modified: self.md.modified().ok(),
+            };
+            let digest = DigestCache::get_or_spawn(key, &self.file)?;
+
+            if let Some(digest) = digest {
+                resp.insert_header((
+                    HeaderName::from_static("repr-digest"),
+                    format!("{}=:{}:", algo.as_str(), digest),
+                ));
+            }
+        }
 
-        if offset != 0 || length != self.md.len() {
+        if partial {
             resp.status(StatusCode::PARTIAL_CONTENT);
+        } else if total_length == 0
+            && self.status_code == StatusCode::OK
+            && self.flags.contains(Flags::EMPTY_NO_CONTENT)
+        {
+            return Ok(resp.status(StatusCode::NO_CONTENT).finish());
+        }
+
+        // the body of a `HEAD` response is discarded so there is no need to read the file; the
+        // sized body still gives the correct `Content-Length`
+        if req.method() == Method::HEAD {
+            return Ok(send_body(
+                resp,
+                length,
+                EmptyStream,
+                self.body_map,
+                self.transformed_length,
+            ));
+        }
+
+        if total_length < self.small_file_threshold
+            && self.bytes_sent_fn.is_none()
+            && self.body_map.is_none()
+        {
+            let body = if length == 0 {
+                Bytes::new()
+            } else {
+                chunked::read_chunk(&self.file, offset, length as usize)?
+            };
+
+            // the file was truncated since it was opened
+            if body.len() as u64 != length {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            return Ok(resp.body(body));
+        }
+
+        #[cfg(feature = "mmap")]
+        if self.flags.contains(Flags::USE_MMAP) {
+            // SAFETY: callers of `use_mmap` are told the file must not be truncated while served;
+            // a failed mapping (e.g. of an empty region) falls back to reading the file
+            if let Ok(stream) = unsafe { MmapStream::new(&self.file, offset, length) } {
+                let stream = stream
+                    .buffer_size(self.buffer_size)
+                    .bytes_sent_fn(self.bytes_sent_fn);
+
+                return Ok(send_body(
+                    resp,
+                    length,
+                    stream,
+                    self.body_map,
+                    self.transformed_length,
+                ));
+            }
+        }
+
+        let change_check = self.change_check();
+        let reader = ChunkedReadFile::new(length, offset, self.file)
+            .buffer_size(self.buffer_size)
+            .read_ahead(self.read_ahead)
+            .bytes_sent_fn(self.bytes_sent_fn)
+            .change_check(change_check);
+
+        Ok(send_body(
+            resp,
+            length,
+            reader,
+            self.body_map,
+            self.transformed_length,
+        ))
+    }
+}
+
+/// Returns the `Accept-Ranges` value to send for a file configured with `flags`, given whether
+/// ranges of its response are refused.
+fn advertised_ranges(flags: Flags, refuse_ranges: bool) -> Option<&'static str> {
+    if !flags.contains(Flags::ADVERTISE_RANGES) {
+        None
+    } else if refuse_ranges {
+        Some("none")
+    } else if flags.contains(Flags::ACCEPT_RANGES) {
+        Some("bytes")
+    } else {
+        None
+    }
+}
+
+/// Returns the digest algorithm preferred by the request's `Want-Repr-Digest` header or, without
+/// one, its `Want-Digest` header.
+fn requested_digest(req: &HttpRequest) -> Option<DigestAlgo> {
+    ["want-repr-digest", "want-digest"]
+        .iter()
+        .map(|name| {
+            req.headers()
+                .get_all(*name)
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+        })
+        .find(|values| !values.is_empty())
+        .and_then(|values| preferred_algo(&values.join(",")))
+}
+
+/// Sends `stream` of `length` bytes as the body of `resp`, transformed by `body_map` if set into
+/// a body of `transformed_length` bytes, if known.
+fn send_body<S>(
+    mut resp: HttpResponseBuilder,
+    length: u64,
+    stream: S,
+    body_map: Option<Rc<BodyMapFn>>,
+    transformed_length: Option<u64>,
+) -> HttpResponse
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin + 'static,
+{
+    match (body_map, transformed_length) {
+        (Some(f), Some(length)) => {
+            resp.body(SizedStream::new(length, MappedStream { stream, f }))
+        }
+        (Some(f), None) => resp.streaming(MappedStream { stream, f }),
+        (None, _) => resp.body(SizedStream::new(length, stream)),
+    }
+}
+
+/// A body stream passing each chunk of another stream through a function.
+struct MappedStream<S> {
+    stream: S,
+    f: Rc<BodyMapFn>,
+}
+
+impl<S> Stream for MappedStream<S>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin,
+{
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => Poll::Ready(Some(Ok((this.f)(chunk)))),
+            poll => poll,
         }
+    }
+}
+
+/// Returns the `Content-Disposition` type used for files of the given MIME type by default.
+fn default_disposition(ct: &mime::Mime) -> DispositionType {
+    match ct.type_() {
+        mime::IMAGE | mime::TEXT | mime::VIDEO => DispositionType::Inline,
+        _ => DispositionType::Attachment,
+    }
+}
+
+/// Replaces the filename parameters of `cd` with those for `filename`.
+fn set_filename(cd: &mut ContentDisposition, filename: &str) {
+    cd.parameters.retain(|param| {
+        !matches!(
+            param,
+            DispositionParam::Filename(_) | DispositionParam::FilenameExt(_)
+        )
+    });
+    cd.parameters.extend(filename_params(filename));
+}
+
+/// Builds the `Content-Disposition` filename parameters for `filename`.
+fn filename_params(filename: &str) -> Vec<DispositionParam> {
+    let safe_filename = sanitize_filename(filename);
+
+    let mut parameters = vec![DispositionParam::Filename(safe_filename.clone())];
+
+    // the unmodified name is still sent when it had to be altered for the plain parameter
+    if safe_filename != filename {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext(String::from("UTF-8")),
+            language_tag: None,
+            value: filename.as_bytes().to_vec(),
+        }))
+    }
+
+    parameters
+}
+
+/// Returns an ASCII-only fallback for the plain `filename` parameter of `Content-Disposition`.
+///
+/// Non-ASCII characters are replaced with underscores, as are control characters and double
+/// quotes, which would break the header or allow injecting into it.
+fn sanitize_filename(filename: &str) -> String {
+    filename
+        .chars()
+        .map(|c| {
+            if !c.is_ascii() || c.is_ascii_control() || c == '"' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
 
-        resp.body(SizedStream::new(length, reader))
+/// A body stream that ends immediately.
+struct EmptyStream;
+
+impl Stream for EmptyStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(None)
     }
 }
 
@@ -503,6 +2059,39 @@ fn none_match(etag: Option<&header::EntityTag>, req: &HttpRequest) -> bool {
     }
 }
 
+/// Returns true if `req` has no `If-Range` header or one which matches `etag` or `last_modified`.
+///
+/// Entity tags are compared using the strong comparison function and dates must match exactly, as
+/// required by RFC 7233 §3.2.
+fn if_range_match(
+    etag: Option<&header::EntityTag>,
+    last_modified: Option<&header::HttpDate>,
+    req: &HttpRequest,
+) -> bool {
+    if !req.headers().contains_key(header::IF_RANGE) {
+        return true;
+    }
+
+    match req.get_header::<header::IfRange>() {
+        Some(header::IfRange::EntityTag(ref item)) => {
+            etag.map_or(false, |etag| item.strong_eq(etag))
+        }
+
+        Some(header::IfRange::Date(since)) => last_modified.map_or(false, |m| {
+            let t1: SystemTime = (*m).into();
+            let t2: SystemTime = since.into();
+
+            match (t1.duration_since(UNIX_EPOCH), t2.duration_since(UNIX_EPOCH)) {
+                (Ok(t1), Ok(t2)) => t1.as_secs() == t2.as_secs(),
+                _ => false,
+            }
+        }),
+
+        // an unparsable validator can not match
+        None => false,
+    }
+}
+
 impl Responder for NamedFile {
     fn respond_to(self, req: &HttpRequest) -> HttpResponse {
         self.into_response(req)
@@ -518,16 +2107,195 @@ impl ServiceFactory<ServiceRequest> for NamedFile {
     type Future = Ready<Result<Self::Service, ()>>;
 
     fn new_service(&self, _: ()) -> Self::Future {
+        // falls back to opening the file for each request if the handle cannot be shared
+        let open_file = if self.flags.contains(Flags::KEEP_OPEN) {
+            self.file.try_clone().ok().map(|file| {
+                RefCell::new(OpenFile {
+                    file,
+                    md: self.md.clone(),
+                    checked: Instant::now(),
+                })
+            })
+        } else {
+            None
+        };
+
+        let allowed_methods = self
+            .allowed_methods
+            .clone()
+            .unwrap_or_else(|| vec![Method::GET, Method::HEAD, Method::OPTIONS]);
+
         ok(NamedFileService {
             path: self.path.clone(),
+            allowed_methods,
+            open_error_handler: self.open_error_handler.clone(),
+            open_file,
+            revalidate_after: self.revalidate_after,
+            settings: FileSettings::new(self),
         })
     }
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
 pub struct NamedFileService {
     path: PathBuf,
+    allowed_methods: Vec<Method>,
+    open_error_handler: Option<Rc<OpenErrorHandler>>,
+    open_file: Option<RefCell<OpenFile>>,
+    revalidate_after: Option<Duration>,
+    settings: FileSettings,
+}
+
+/// The configuration of a `NamedFile`, reapplied by a `NamedFileService` to the file it opens for
+/// each request.
+struct FileSettings {
+    // only kept if set explicitly, since the file may have changed since
+    modified: Option<SystemTime>,
+    flags: Flags,
+    status_code: StatusCode,
+    content_type: mime::Mime,
+    content_disposition: header::ContentDisposition,
+    disposition_policy: DispositionPolicy,
+    encoding: Option<ContentEncoding>,
+    cache_control: Option<header::CacheControl>,
+    expires: Option<Duration>,
+    etag_fn: Option<Rc<EtagFn>>,
+    bytes_sent_fn: Option<Rc<BytesSentFn>>,
+    buffer_size: usize,
+    read_ahead: usize,
+    small_file_threshold: u64,
+    content_length: Option<u64>,
+    range: Option<HttpRange>,
+    charset: Option<header::Charset>,
+    content_language: Option<header::LanguageTag>,
+    max_ranges: usize,
+    change_check_interval: Duration,
+    digest: Option<DigestMode>,
+    body_map: Option<Rc<BodyMapFn>>,
+    transformed_length: Option<u64>,
+}
+
+impl FileSettings {
+    fn new(file: &NamedFile) -> Self {
+        Self {
+            modified: file
+                .modified
+                .filter(|&mtime| file.md.modified().ok() != Some(mtime)),
+            flags: file.flags,
+            status_code: file.status_code,
+            content_type: file.content_type.clone(),
+            content_disposition: file.content_disposition.clone(),
+            disposition_policy: file.disposition_policy,
+            encoding: file.encoding,
+            cache_control: file.cache_control.clone(),
+            expires: file.expires,
+            etag_fn: file.etag_fn.clone(),
+            bytes_sent_fn: file.bytes_sent_fn.clone(),
+            buffer_size: file.buffer_size,
+            read_ahead: file.read_ahead,
+            small_file_threshold: file.small_file_threshold,
+            content_length: file.content_length,
+            range: file.range,
+            charset: file.charset.clone(),
+            content_language: file.content_language.clone(),
+            max_ranges: file.max_ranges,
+            change_check_interval: file.change_check_interval,
+            digest: file.digest,
+            body_map: file.body_map.clone(),
+            transformed_length: file.transformed_length,
+        }
+    }
+
+    /// Applies the settings to `file`, which is always the file at the service's path and never a
+    /// precompressed variant.
+    fn apply(&self, file: &mut NamedFile) {
+        if self.modified.is_some() {
+            file.modified = self.modified;
+        }
+
+        file.flags = self.flags;
+        file.status_code = self.status_code;
+        file.content_type = self.content_type.clone();
+        file.content_disposition = self.content_disposition.clone();
+        file.disposition_policy = self.disposition_policy;
+        file.encoding = self.encoding;
+        file.cache_control = self.cache_control.clone();
+        file.expires = self.expires;
+        file.etag_fn = self.etag_fn.clone();
+        file.bytes_sent_fn = self.bytes_sent_fn.clone();
+        file.buffer_size = self.buffer_size;
+        file.read_ahead = self.read_ahead;
+        file.small_file_threshold = self.small_file_threshold;
+        file.content_length = self.content_length;
+        file.range = self.range;
+        file.charset = self.charset.clone();
+        file.content_language = self.content_language.clone();
+        file.max_ranges = self.max_ranges;
+        file.change_check_interval = self.change_check_interval;
+        file.digest = self.digest;
+        file.body_map = self.body_map.clone();
+        file.transformed_length = self.transformed_length;
+    }
+}
+
+/// A file handle kept open by a `NamedFileService`.
+struct OpenFile {
+    file: File,
+    md: Metadata,
+    checked: Instant,
+}
+
+impl NamedFileService {
+    fn open(&self) -> io::Result<NamedFile> {
+        let open_file = match self.open_file {
+            Some(ref open_file) => open_file,
+            None => return NamedFile::open(&self.path),
+        };
+
+        let mut open_file = open_file.borrow_mut();
+
+        if let Some(interval) = self.revalidate_after {
+            if open_file.checked.elapsed() >= interval {
+                let md = fs::metadata(&self.path)?;
+
+                if !same_version(&md, &open_file.md) {
+                    let file = File::open(&self.path)?;
+                    open_file.md = file.metadata()?;
+                    open_file.file = file;
+                }
+
+                open_file.checked = Instant::now();
+            }
+        }
+
+        // reads are positioned, so the clone can be read alongside other responses' handles
+        NamedFile::from_file_with_metadata(
+            open_file.file.try_clone()?,
+            &self.path,
+            open_file.md.clone(),
+        )
+    }
+}
+
+/// Returns true if both metadata describe the same, unmodified file.
+pub(crate) fn same_version(a: &Metadata, b: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        if a.dev() != b.dev() || a.ino() != b.ino() {
+            return false;
+        }
+    }
+
+    a.len() == b.len() && a.modified().ok() == b.modified().ok()
+}
+
+impl fmt::Debug for NamedFileService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NamedFileService")
+            .field("path", &self.path)
+            .field("allowed_methods", &self.allowed_methods)
+            .finish()
+    }
 }
 
 impl Service<ServiceRequest> for NamedFileService {
@@ -539,22 +2307,66 @@ impl Service<ServiceRequest> for NamedFileService {
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let (req, _) = req.into_parts();
-        ready(
-            NamedFile::open(&self.path)
-                .map_err(|e| e.into())
-                .map(|f| f.into_response(&req))
-                .map(|res| ServiceResponse::new(req, res)),
-        )
+
+        if !self.allowed_methods.contains(req.method()) {
+            let res = HttpResponse::MethodNotAllowed()
+                .insert_header(header::Allow(self.allowed_methods.clone()))
+                .finish();
+
+            return ok(ServiceResponse::new(req, res));
+        }
+
+        if req.method() == Method::OPTIONS {
+            let mut res = HttpResponse::NoContent();
+            res.insert_header(header::Allow(self.allowed_methods.clone()));
+
+            // the served file is never a precompressed variant, so only a body map refuses ranges
+            let refuse_ranges = self.settings.body_map.is_some();
+            if let Some(value) = advertised_ranges(self.settings.flags, refuse_ranges) {
+                res.insert_header((header::ACCEPT_RANGES, value));
+            }
+
+            let res = res.finish();
+
+            return ok(ServiceResponse::new(req, res));
+        }
+
+        let res = match self.open() {
+            Ok(mut file) => {
+                self.settings.apply(&mut file);
+                file.into_response(&req)
+            }
+            Err(err) => match self.open_error_handler {
+                Some(ref handler) => handler(err, &req),
+                None => HttpResponse::new(open_error_status(&err)),
+            },
+        };
+
+        ok(ServiceResponse::new(req, res))
+    }
+}
+
+/// Returns the status code of the response sent when a file could not be opened.
+pub(crate) fn open_error_status(err: &io::Error) -> StatusCode {
+    match err.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
 
 impl HttpServiceFactory for NamedFile {
     fn register(self, config: &mut AppService) {
-        config.register_service(
-            ResourceDef::root_prefix(self.path.to_string_lossy().as_ref()),
-            None,
-            self,
-            None,
-        )
+        let rdef = match self.mount_path {
+            Some(ref path)
+                if !path.starts_with('/') && (config.is_root() || !path.is_empty()) =>
+            {
+                ResourceDef::new(format!("/{}", path))
+            }
+            Some(ref path) => ResourceDef::new(path.as_str()),
+            None => ResourceDef::root_prefix(self.path.to_string_lossy().as_ref()),
+        };
+
+        config.register_service(rdef, None, self, None)
     }
 }
@@ -1,6 +1,11 @@
+use std::cmp;
+
 use derive_more::{Display, Error};
 
 /// HTTP Range header representation.
+///
+/// A range covers `length` bytes from the `start` offset; the last byte it includes is at
+/// `start + length - 1`.
 #[derive(Debug, Clone, Copy)]
 pub struct HttpRange {
     /// Start of range.
@@ -10,35 +15,192 @@ pub struct HttpRange {
     pub length: u64,
 }
 
-#[derive(Debug, Clone, Display, Error)]
-#[display(fmt = "Parse HTTP Range failed")]
-pub struct ParseRangeErr(#[error(not(source))] ());
+/// Error returned by [`HttpRange::parse`] when a `Range` header cannot be satisfied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Error)]
+pub enum HttpRangeError {
+    /// The header is not a valid range specifier.
+    #[display(fmt = "Range header is malformed")]
+    InvalidSyntax,
+
+    /// The header uses a range unit other than `bytes`.
+    #[display(fmt = "Range header uses an unsupported unit")]
+    UnsupportedUnit,
+
+    /// None of the ranges overlap the representation.
+    #[display(fmt = "Range header has no satisfiable ranges")]
+    NoOverlap,
+}
 
 impl HttpRange {
-    /// Parses Range HTTP header string as per RFC 2616.
+    /// Parses Range HTTP header string as per RFC 7233.
     ///
-    /// `header` is HTTP Range header (e.g. `bytes=bytes=0-9`).
+    /// `header` is HTTP Range header (e.g. `bytes=0-9`).
     /// `size` is full size of response (file).
-    pub fn parse(header: &str, size: u64) -> Result<Vec<HttpRange>, ParseRangeErr> {
+    ///
+    /// Ranges are returned in the order they appear in the header, without being merged. Ranges
+    /// whose end is past `size` are shortened to end with the last byte.
+    ///
+    /// Suffix ranges (e.g. `bytes=-500`) are resolved to the last bytes of the file and are clamped
+    /// to the whole file when longer than `size`.
+    ///
+    /// # Errors
+    /// Headers using a unit other than `bytes` fail with [`HttpRangeError::UnsupportedUnit`] and
+    /// malformed ones with [`HttpRangeError::InvalidSyntax`]. If none of the ranges are
+    /// satisfiable, which is always the case when `size` is zero, [`HttpRangeError::NoOverlap`] is
+    /// returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use actix_files::{HttpRange, HttpRangeError};
+    ///
+    /// let ranges = HttpRange::parse("bytes=0-9, -5", 100).unwrap();
+    /// assert_eq!((ranges[0].start, ranges[0].length), (0, 10));
+    /// assert_eq!((ranges[1].start, ranges[1].length), (95, 5));
+    ///
+    /// assert_eq!(
+    ///     HttpRange::parse("bytes=200-", 100).unwrap_err(),
+    ///     HttpRangeError::NoOverlap,
+    /// );
+    /// ```
+    pub fn parse(header: &str, size: u64) -> Result<Vec<HttpRange>, HttpRangeError> {
+        match header.find('=').map(|eq| &header[..eq]) {
+            Some("bytes") => {}
+            Some(unit) if !unit.is_empty() && !unit.contains(char::is_whitespace) => {
+                return Err(HttpRangeError::UnsupportedUnit)
+            }
+            _ => return Err(HttpRangeError::InvalidSyntax),
+        }
+
         match http_range::HttpRange::parse(header, size) {
-            Ok(ranges) => Ok(ranges
-                .iter()
-                .map(|range| HttpRange {
-                    start: range.start,
-                    length: range.length,
-                })
-                .collect()),
-            Err(_) => Err(ParseRangeErr(())),
+            Ok(ranges) => {
+                // nothing of an empty entity can be satisfied
+                let ranges = ranges
+                    .iter()
+                    .filter(|range| range.length > 0)
+                    .map(|range| HttpRange {
+                        start: range.start,
+                        length: range.length,
+                    })
+                    .collect::<Vec<_>>();
+
+                if ranges.is_empty() {
+                    Err(HttpRangeError::NoOverlap)
+                } else {
+                    Ok(ranges)
+                }
+            }
+            Err(http_range::HttpRangeParseError::NoOverlap) => Err(HttpRangeError::NoOverlap),
+            Err(http_range::HttpRangeParseError::InvalidRange) => {
+                Err(HttpRangeError::InvalidSyntax)
+            }
         }
     }
 }
 
+/// Sorts ranges by start offset and merges those that overlap or are adjacent.
+pub(crate) fn coalesce(mut ranges: Vec<HttpRange>) -> Vec<HttpRange> {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<HttpRange> = Vec::with_capacity(ranges.len());
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.start + last.length => {
+                let end = cmp::max(last.start + last.length, range.start + range.length);
+                last.length = end - last.start;
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Returns the range to serve for the ranges of a `Range` header, given in request order.
+///
+/// Only one range is served: the first one requested, merged with the ranges it overlaps or
+/// adjoins if `merge` is set. `None` is returned if more than `max_ranges` ranges remain.
+pub(crate) fn select(
+    ranges: Vec<HttpRange>,
+    merge: bool,
+    max_ranges: usize,
+) -> Option<HttpRange> {
+    let first = ranges[0];
+    let ranges = if merge { coalesce(ranges) } else { ranges };
+
+    if ranges.len() > max_ranges {
+        return None;
+    }
+
+    // merged ranges are sorted, so find the one the first requested range ended up in
+    ranges
+        .into_iter()
+        .find(|range| range.start <= first.start && first.start < range.start + range.length)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     struct T(&'static str, u64, Vec<HttpRange>);
 
+    fn spans(ranges: Vec<HttpRange>) -> Vec<(u64, u64)> {
+        ranges.iter().map(|r| (r.start, r.length)).collect()
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let parse = |header| coalesce(HttpRange::parse(header, 1000).unwrap());
+
+        // nested
+        assert_eq!(spans(parse("bytes=0-100,20-30")), vec![(0, 101)]);
+        // partially overlapping, out of order
+        assert_eq!(spans(parse("bytes=50-200,0-100")), vec![(0, 201)]);
+        // touching
+        assert_eq!(spans(parse("bytes=0-9,10-19")), vec![(0, 20)]);
+        // disjoint ranges are kept, sorted
+        assert_eq!(
+            spans(parse("bytes=500-599,0-9,11-20")),
+            vec![(0, 10), (11, 10), (500, 100)]
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let select = |header, merge, max| {
+            select(HttpRange::parse(header, 1000).unwrap(), merge, max)
+                .map(|range| (range.start, range.length))
+        };
+
+        // the first requested range is served rather than the lowest one
+        assert_eq!(select("bytes=500-599,0-9", true, 10), Some((500, 100)));
+        assert_eq!(select("bytes=500-599,0-9", false, 10), Some((500, 100)));
+        // along with the ranges merged into it
+        assert_eq!(select("bytes=500-599,0-9,550-", true, 10), Some((500, 500)));
+
+        // the limit applies to the merged ranges
+        assert_eq!(select("bytes=0-9,5-19,10-29", true, 1), Some((0, 30)));
+        assert_eq!(select("bytes=0-9,5-19,10-29", false, 1), None);
+        assert_eq!(select("bytes=0-9,20-29", true, 1), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |header| HttpRange::parse(header, 10).unwrap_err();
+
+        assert_eq!(err("bytes=20-"), HttpRangeError::NoOverlap);
+        assert_eq!(err("bytes=-0"), HttpRangeError::NoOverlap);
+        assert_eq!(
+            HttpRange::parse("bytes=0-", 0).unwrap_err(),
+            HttpRangeError::NoOverlap
+        );
+        assert_eq!(err("bytes=5-4"), HttpRangeError::InvalidSyntax);
+        assert_eq!(err("bytes=A-Z"), HttpRangeError::InvalidSyntax);
+        assert_eq!(err("foo"), HttpRangeError::InvalidSyntax);
+        assert_eq!(err(""), HttpRangeError::InvalidSyntax);
+        assert_eq!(err("items=0-9"), HttpRangeError::UnsupportedUnit);
+    }
+
     #[test]
     fn test_parse() {
         let tests = vec![
@@ -61,6 +223,10 @@ mod tests {
             T("bytes=Ran-dom, garbage", 10, vec![]),
             T("bytes=0x01-0x02", 10, vec![]),
             T("bytes=         ", 10, vec![]),
+            T("bytes=-0", 10, vec![]),
+            T("bytes=0-", 0, vec![]),
+            T("bytes=0-0", 0, vec![]),
+            T("bytes=-1", 0, vec![]),
             T("bytes= , , ,   ", 10, vec![]),
             T(
                 "bytes=0-9",
@@ -266,20 +432,11 @@ mod tests {
 
             let res = HttpRange::parse(header, size);
 
-            if res.is_err() {
-                if expected.is_empty() {
-                    continue;
-                } else {
-                    panic!(
-                        "parse({}, {}) returned error {:?}",
-                        header,
-                        size,
-                        res.unwrap_err()
-                    );
-                }
-            }
-
-            let got = res.unwrap();
+            let got = match res {
+                Ok(got) => got,
+                Err(_) if expected.is_empty() => continue,
+                Err(err) => panic!("parse({}, {}) returned error {:?}", header, size, err),
+            };
 
             if got.len() != expected.len() {
                 panic!(
@@ -0,0 +1,148 @@
+use std::{borrow::Cow, rc::Rc};
+
+use actix_service::fn_service;
+use actix_utils::future::ok;
+use actix_web::{
+    dev::{AppService, HttpServiceFactory, ResourceDef, ServiceRequest},
+    http::{header, StatusCode},
+    HttpResponse,
+};
+
+/// Redirect service for static assets that have moved.
+///
+/// Requests for `from`, or any path below it, are redirected to the same path below `to`. The
+/// query string is kept. `308 Permanent Redirect` is sent unless another status code is set with
+/// [`status_code`](Self::status_code), such as `301 Moved Permanently` for older clients.
+///
+/// # Examples
+/// ```
+/// use actix_web::{http::StatusCode, App};
+/// use actix_files::{Files, Redirect};
+///
+/// let app = App::new()
+///     // `/assets/img/logo.png?v=2` is redirected to `/static/img/logo.png?v=2`
+///     .service(Redirect::new("/assets", "/static"))
+///     .service(Redirect::new("/favicon.ico", "/static/favicon.ico")
+///         .status_code(StatusCode::MOVED_PERMANENTLY))
+///     .service(Files::new("/static", "."));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    from: String,
+    to: Rc<str>,
+    status_code: StatusCode,
+}
+
+impl Redirect {
+    /// Create new `Redirect` instance for requests to `from`.
+    ///
+    /// `to` is a path or absolute URL without a query string. A trailing slash is ignored when
+    /// the remainder of a longer path is appended to it.
+    pub fn new<T: Into<String>>(from: &str, to: T) -> Self {
+        Self {
+            from: from.trim_end_matches('/').to_owned(),
+            to: to.into().into(),
+            status_code: StatusCode::PERMANENT_REDIRECT,
+        }
+    }
+
+    /// Set the status code sent with redirects.
+    ///
+    /// # Panics
+    /// Panics if `status_code` is not a redirection (3xx) status code.
+    pub fn status_code(mut self, status_code: StatusCode) -> Self {
+        assert!(
+            status_code.is_redirection(),
+            "redirect status code must be 3xx, got {}",
+            status_code
+        );
+        self.status_code = status_code;
+        self
+    }
+}
+
+/// Collapses the leading slashes of a request path into one.
+///
+/// A location starting with `//` (or `/\`, which browsers treat alike) would be resolved as a URL
+/// on another host, so paths are passed through this before being redirected to.
+pub(crate) fn collapse_leading_slashes(path: &str) -> Cow<'_, str> {
+    let rest = path.trim_start_matches(&['/', '\\'][..]);
+
+    if rest.len() < path.len() {
+        Cow::Owned(format!("/{}", rest))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Returns the location `path` is redirected to, with `query` appended when not empty.
+fn location(to: &str, path: &str, query: &str) -> String {
+    let path = collapse_leading_slashes(path);
+    let path = path.as_ref();
+
+    let mut location = if path.is_empty() || (path == "/" && to.ends_with('/')) {
+        to.to_owned()
+    } else {
+        format!("{}{}", to.trim_end_matches('/'), path)
+    };
+
+    if !query.is_empty() {
+        location.push('?');
+        location.push_str(query);
+    }
+
+    location
+}
+
+impl HttpServiceFactory for Redirect {
+    fn register(self, config: &mut AppService) {
+        let rdef = if config.is_root() {
+            ResourceDef::root_prefix(&self.from)
+        } else {
+            ResourceDef::prefix(&self.from)
+        };
+
+        let to = self.to;
+        let status_code = self.status_code;
+
+        config.register_service(
+            rdef,
+            None,
+            fn_service(move |req: ServiceRequest| {
+                // prefixes only match whole segments, so what remains is empty or starts with `/`
+                let location = location(&to, req.match_info().path(), req.query_string());
+                ok(req.into_response(
+                    HttpResponse::build(status_code)
+                        .insert_header((header::LOCATION, location))
+                        .finish(),
+                ))
+            }),
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        assert_eq!(location("/new", "", ""), "/new");
+        assert_eq!(location("/new/", "", ""), "/new/");
+        assert_eq!(location("/new", "/", ""), "/new/");
+        assert_eq!(location("/new/", "/", ""), "/new/");
+        assert_eq!(location("/new/", "/img/a.png", "v=2"), "/new/img/a.png?v=2");
+        assert_eq!(location("/", "/a.js", ""), "/a.js");
+        assert_eq!(
+            location("https://cdn.example.com", "/a.js", "v=1"),
+            "https://cdn.example.com/a.js?v=1"
+        );
+
+        // must not become a protocol-relative URL for another host
+        assert_eq!(location("/", "//evil.com", ""), "/evil.com");
+        assert_eq!(location("/", "/\\evil.com", ""), "/evil.com");
+        assert_eq!(location("", "///evil.com/a", ""), "/evil.com/a");
+        assert_eq!(location("/new", "//", ""), "/new/");
+    }
+}
@@ -1,4 +1,8 @@
-use std::{fmt, io, path::PathBuf, rc::Rc};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use actix_service::Service;
 use actix_utils::future::ok;
@@ -6,28 +10,58 @@ use actix_web::{
     dev::{ServiceRequest, ServiceResponse},
     error::Error,
     guard::Guard,
-    http::{header, Method},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
     HttpResponse,
 };
 use futures_core::future::LocalBoxFuture;
 
 use crate::{
-    named, Directory, DirectoryRenderer, FilesError, HttpService, MimeOverride, NamedFile,
-    PathBufWrap,
+    cache::{NegativeCache, OpenFileCache},
+    error::UriSegmentError,
+    named, redirect, ContentTypeOverride, Directory, DirectoryRenderer, DispositionOverride,
+    FilesError, HttpService, NamedFile, PathBufWrap, PathFilter,
 };
 
+/// Which symlinks a `Files` service follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Symlinks {
+    /// No symlinks are followed.
+    Disabled,
+
+    /// Symlinks are followed as long as they resolve to a path inside the served directory.
+    Contained,
+
+    /// All symlinks are followed.
+    Followed,
+}
+
+/// Methods accepted when no method guard is set.
+const DEFAULT_METHODS: &[Method] = &[Method::GET, Method::HEAD, Method::OPTIONS];
+
 /// Assembled file serving service.
 pub struct FilesService {
     pub(crate) directory: PathBuf,
-    pub(crate) index: Option<String>,
+    pub(crate) index: Vec<String>,
     pub(crate) show_index: bool,
     pub(crate) redirect_to_slash: bool,
     pub(crate) default: Option<HttpService>,
     pub(crate) renderer: Rc<DirectoryRenderer>,
-    pub(crate) mime_override: Option<Rc<MimeOverride>>,
+    pub(crate) mime_override: Option<Rc<DispositionOverride>>,
+    pub(crate) content_type_override: Option<Rc<ContentTypeOverride>>,
     pub(crate) file_flags: named::Flags,
     pub(crate) guards: Option<Rc<dyn Guard>>,
     pub(crate) hidden_files: bool,
+    pub(crate) path_filter: Option<Rc<PathFilter>>,
+    pub(crate) symlinks: Symlinks,
+    pub(crate) cache_control: Option<header::CacheControl>,
+    pub(crate) precompressed: bool,
+    pub(crate) default_content_type: Option<mime::Mime>,
+    pub(crate) strip_prefix_segments: usize,
+    pub(crate) not_found_file: Option<PathBuf>,
+    pub(crate) response_headers: Vec<(HeaderName, HeaderValue)>,
+    pub(crate) directory_status: StatusCode,
+    pub(crate) negative_cache: Option<NegativeCache>,
+    pub(crate) open_file_cache: Option<OpenFileCache>,
 }
 
 impl FilesService {
@@ -38,12 +72,154 @@ impl FilesService {
     ) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
         log::debug!("error handling {}: {}", req.path(), err);
 
+        if err.kind() == io::ErrorKind::NotFound {
+            if let Some(ref path) = self.not_found_file {
+                match NamedFile::open(path) {
+                    Ok(named_file) => {
+                        // the page stands in for a missing file, so it has no validators that a
+                        // conditional request could match
+                        let named_file = self
+                            .configure_named_file(named_file)
+                            .set_status_code(StatusCode::NOT_FOUND)
+                            .use_etag(false)
+                            .use_last_modified(false)
+                            .conditional_custom_status(false);
+                        return self.send_named_file(named_file, req);
+                    }
+                    Err(err) => log::debug!("error opening {:?}: {}", path, err),
+                }
+            }
+        }
+
         if let Some(ref default) = self.default {
             Box::pin(default.call(req))
         } else {
             Box::pin(ok(req.error_response(err)))
         }
     }
+
+    /// Remembers `path` as missing if `err` says it is and missing paths are cached.
+    fn record_missing(&self, path: &Path, err: &io::Error) {
+        if let Some(ref cache) = self.negative_cache {
+            if err.kind() == io::ErrorKind::NotFound {
+                cache.insert(path.to_owned());
+            }
+        }
+    }
+
+    /// Fails with `NotFound` if `path` does not exist or resolves to a location that may not be
+    /// served, i.e. outside the served directory or, with symlinks disabled, through a symlink.
+    fn check_path(&self, path: &Path) -> io::Result<()> {
+        let canonical = path.canonicalize()?;
+
+        let allowed = match self.symlinks {
+            // paths are joined from canonical parts, so they only differ when resolving a symlink
+            Symlinks::Disabled => canonical == path,
+            Symlinks::Contained => canonical.starts_with(&self.directory),
+            // joining a path with a prefix or root (e.g. `C:` on Windows) replaces the directory
+            Symlinks::Followed => path.starts_with(&self.directory),
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::NotFound.into())
+        }
+    }
+
+    /// Returns the file at `path` if it is in the open file cache and was checked recently enough.
+    ///
+    /// Files are only cached once [`check_path`](Self::check_path) allowed them, so the check is
+    /// skipped for them until the cache checks the file system again.
+    fn cached_file(&self, path: &Path) -> Option<io::Result<NamedFile>> {
+        if self.precompressed {
+            return None;
+        }
+
+        let (file, md) = self.open_file_cache.as_ref()?.get_fresh(path)?;
+        Some(NamedFile::from_file_with_metadata(file, path, md))
+    }
+
+    fn open_file(&self, path: &Path, req: &ServiceRequest) -> io::Result<NamedFile> {
+        if self.precompressed {
+            NamedFile::open_precompressed(path, req.headers())
+        } else if let Some(ref cache) = self.open_file_cache {
+            let (file, md) = cache.open(path)?;
+            NamedFile::from_file_with_metadata(file, path, md)
+        } else {
+            NamedFile::open(path)
+        }
+    }
+
+    fn serve_named_file(
+        &self,
+        named_file: NamedFile,
+        req: ServiceRequest,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
+        let named_file = self.configure_named_file(named_file);
+        self.send_named_file(named_file, req)
+    }
+
+    /// Applies the settings of the service to `named_file`.
+    fn configure_named_file(&self, mut named_file: NamedFile) -> NamedFile {
+        let content_type = self
+            .content_type_override
+            .as_ref()
+            .and_then(|content_type_override| content_type_override(named_file.path()));
+
+        if let Some(content_type) = content_type {
+            named_file = named_file.set_content_type_and_disposition(content_type);
+        } else if let Some(ref content_type) = self.default_content_type {
+            if named_file.content_type == mime::APPLICATION_OCTET_STREAM {
+                named_file = named_file.set_content_type_and_disposition(content_type.clone());
+            }
+        }
+
+        if let Some(ref mime_override) = self.mime_override {
+            let new_disposition = mime_override(&named_file.content_type.type_());
+            named_file.content_disposition.disposition = new_disposition;
+        }
+        named_file.flags = self.file_flags;
+        named_file.cache_control = self.cache_control.clone();
+        named_file
+    }
+
+    fn send_named_file(
+        &self,
+        named_file: NamedFile,
+        req: ServiceRequest,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
+        let (req, _) = req.into_parts();
+        let mut res = named_file.into_response(&req);
+        self.add_response_headers(res.headers_mut());
+        Box::pin(ok(ServiceResponse::new(req, res)))
+    }
+
+    /// Appends the configured response headers that are not already set in `headers`.
+    fn add_response_headers(&self, headers: &mut HeaderMap) {
+        let mut added = Vec::new();
+
+        for (name, value) in &self.response_headers {
+            if headers.contains_key(name) && !added.contains(&name) {
+                continue;
+            }
+
+            headers.append(name.clone(), value.clone());
+            added.push(name);
+        }
+    }
+}
+
+/// Returns `path` without its first `n` non-empty segments, or `None` if it has no more than `n`.
+fn strip_segments(path: &str, n: usize) -> Option<&str> {
+    let mut rest = path;
+
+    for _ in 0..n {
+        rest = rest.trim_start_matches('/');
+        rest = &rest[rest.find('/')?..];
+    }
+
+    Some(rest)
 }
 
 impl fmt::Debug for FilesService {
@@ -60,64 +236,115 @@ impl Service<ServiceRequest> for FilesService {
     actix_service::always_ready!();
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let is_method_valid = if let Some(guard) = &self.guards {
+        if let Some(guard) = &self.guards {
             // execute user defined guards
-            (**guard).check(req.head())
+            if !(**guard).check(req.head()) {
+                return Box::pin(ok(req.into_response(
+                    HttpResponse::MethodNotAllowed()
+                        .insert_header(header::ContentType(mime::TEXT_PLAIN_UTF_8))
+                        .body("Request did not meet this resource's requirements."),
+                )));
+            }
         } else {
             // default behavior
-            matches!(*req.method(), Method::HEAD | Method::GET)
-        };
-
-        if !is_method_valid {
-            return Box::pin(ok(req.into_response(
-                actix_web::HttpResponse::MethodNotAllowed()
-                    .insert_header(header::ContentType(mime::TEXT_PLAIN_UTF_8))
-                    .body("Request did not meet this resource's requirements."),
-            )));
+            match *req.method() {
+                Method::GET | Method::HEAD => {}
+                Method::OPTIONS => {
+                    return Box::pin(ok(req.into_response(
+                        HttpResponse::NoContent()
+                            .insert_header(header::Allow(DEFAULT_METHODS.to_vec()))
+                            .finish(),
+                    )));
+                }
+                _ => {
+                    return Box::pin(ok(req.into_response(
+                        HttpResponse::MethodNotAllowed()
+                            .insert_header(header::Allow(DEFAULT_METHODS.to_vec()))
+                            .insert_header(header::ContentType(mime::TEXT_PLAIN_UTF_8))
+                            .body("Request did not meet this resource's requirements."),
+                    )));
+                }
+            }
         }
 
         let real_path =
-            match PathBufWrap::parse_path(req.match_info().path(), self.hidden_files) {
-                Ok(item) => item,
-                Err(e) => return Box::pin(ok(req.error_response(e))),
+            match strip_segments(req.match_info().path(), self.strip_prefix_segments) {
+                Some(path) => PathBufWrap::parse_path(path, self.hidden_files),
+                None => return self.handle_err(io::ErrorKind::NotFound.into(), req),
             };
 
+        let real_path = match real_path {
+            Ok(item) => item,
+            // hidden files are reported as missing rather than revealing that they exist
+            Err(UriSegmentError::BadStart('.')) => {
+                return self.handle_err(io::ErrorKind::NotFound.into(), req)
+            }
+            Err(e) => return Box::pin(ok(req.error_response(e))),
+        };
+
+        if let Some(ref filter) = self.path_filter {
+            if !filter(real_path.as_ref(), req.head()) {
+                return self.handle_err(io::ErrorKind::NotFound.into(), req);
+            }
+        }
+
         // full file path
         let path = self.directory.join(&real_path);
-        if let Err(err) = path.canonicalize() {
-            return Box::pin(self.handle_err(err, req));
+
+        if let Some(ref cache) = self.negative_cache {
+            if cache.contains(&path) {
+                return self.handle_err(io::ErrorKind::NotFound.into(), req);
+            }
+        }
+
+        if let Some(res) = self.cached_file(&path) {
+            return match res {
+                Ok(named_file) => self.serve_named_file(named_file, req),
+                Err(err) => self.handle_err(err, req),
+            };
+        }
+
+        if let Err(err) = self.check_path(&path) {
+            self.record_missing(&path, &err);
+            return self.handle_err(err, req);
         }
 
         if path.is_dir() {
             if self.redirect_to_slash
                 && !req.path().ends_with('/')
-                && (self.index.is_some() || self.show_index)
+                && (!self.index.is_empty() || self.show_index)
             {
-                let redirect_to = format!("{}/", req.path());
+                let path = redirect::collapse_leading_slashes(req.path());
+                let redirect_to = match req.query_string() {
+                    "" => format!("{}/", path),
+                    query => format!("{}/?{}", path, query),
+                };
 
                 return Box::pin(ok(req.into_response(
-                    HttpResponse::Found()
+                    HttpResponse::PermanentRedirect()
                         .insert_header((header::LOCATION, redirect_to))
                         .finish(),
                 )));
             }
 
-            if let Some(ref redir_index) = self.index {
-                let path = path.join(redir_index);
-
-                match NamedFile::open(path) {
-                    Ok(mut named_file) => {
-                        if let Some(ref mime_override) = self.mime_override {
-                            let new_disposition =
-                                mime_override(&named_file.content_type.type_());
-                            named_file.content_disposition.disposition = new_disposition;
-                        }
-                        named_file.flags = self.file_flags;
-
-                        let (req, _) = req.into_parts();
-                        let res = named_file.into_response(&req);
-                        Box::pin(ok(ServiceResponse::new(req, res)))
+            if !self.index.is_empty() {
+                // candidates are tried in order; only missing files move on to the next one
+                let mut res = Err(io::ErrorKind::NotFound.into());
+                for index in &self.index {
+                    let path = path.join(index);
+                    res = match self.cached_file(&path) {
+                        Some(res) => res,
+                        None => self
+                            .check_path(&path)
+                            .and_then(|_| self.open_file(&path, &req)),
+                    };
+                    if !matches!(&res, Err(err) if err.kind() == io::ErrorKind::NotFound) {
+                        break;
                     }
+                }
+
+                match res {
+                    Ok(named_file) => self.serve_named_file(named_file, req),
                     Err(err) => self.handle_err(err, req),
                 }
             } else if self.show_index {
@@ -127,29 +354,25 @@ impl Service<ServiceRequest> for FilesService {
                 let x = (self.renderer)(&dir, &req);
 
                 Box::pin(match x {
-                    Ok(resp) => ok(resp),
+                    Ok(mut resp) => {
+                        self.add_response_headers(resp.headers_mut());
+                        ok(resp)
+                    }
                     Err(err) => ok(ServiceResponse::from_err(err, req)),
                 })
             } else {
-                Box::pin(ok(ServiceResponse::from_err(
-                    FilesError::IsDirectory,
-                    req.into_parts().0,
-                )))
+                let mut res =
+                    ServiceResponse::from_err(FilesError::IsDirectory, req.into_parts().0);
+                *res.response_mut().status_mut() = self.directory_status;
+                Box::pin(ok(res))
             }
         } else {
-            match NamedFile::open(path) {
-                Ok(mut named_file) => {
-                    if let Some(ref mime_override) = self.mime_override {
-                        let new_disposition = mime_override(&named_file.content_type.type_());
-                        named_file.content_disposition.disposition = new_disposition;
-                    }
-                    named_file.flags = self.file_flags;
-
-                    let (req, _) = req.into_parts();
-                    let res = named_file.into_response(&req);
-                    Box::pin(ok(ServiceResponse::new(req, res)))
+            match self.open_file(&path, &req) {
+                Ok(named_file) => self.serve_named_file(named_file, req),
+                Err(err) => {
+                    self.record_missing(&path, &err);
+                    self.handle_err(err, req)
                 }
-                Err(err) => self.handle_err(err, req),
             }
         }
     }
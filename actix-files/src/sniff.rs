@@ -0,0 +1,192 @@
+use mime::Mime;
+
+/// Number of bytes at the start of a file needed to match every signature.
+pub(crate) const SNIFF_LEN: usize = 512;
+
+/// The bytes expected at given offsets of a file, and the MIME type they identify.
+type Signature = (&'static [(usize, &'static [u8])], &'static str);
+
+/// Signatures of common formats.
+///
+/// Only binary formats with distinctive signatures are listed; text formats such as HTML cannot
+/// be told apart reliably and are left to the extension. ISO base media files are told apart by
+/// their major brand, and files with an unknown brand are not recognized.
+const SIGNATURES: &[Signature] = &[
+    (&[(0, b"\x89PNG\r\n\x1a\n")], "image/png"),
+    (&[(0, b"\xff\xd8\xff")], "image/jpeg"),
+    (&[(0, b"GIF87a")], "image/gif"),
+    (&[(0, b"GIF89a")], "image/gif"),
+    (&[(0, b"RIFF"), (8, b"WEBP")], "image/webp"),
+    (&[(0, b"\x00\x00\x01\x00")], "image/x-icon"),
+    (&[(0, b"%PDF-")], "application/pdf"),
+    (&[(0, b"PK\x03\x04")], "application/zip"),
+    (&[(0, b"\x1f\x8b")], "application/gzip"),
+    (&[(0, b"7z\xbc\xaf\x27\x1c")], "application/x-7z-compressed"),
+    (&[(257, b"ustar")], "application/x-tar"),
+    (&[(0, b"\x00asm")], "application/wasm"),
+    (&[(0, b"wOFF")], "font/woff"),
+    (&[(0, b"wOF2")], "font/woff2"),
+    (&[(4, b"ftypavif")], "image/avif"),
+    (&[(4, b"ftypavis")], "image/avif"),
+    (&[(4, b"ftypheic")], "image/heic"),
+    (&[(4, b"ftypheix")], "image/heic"),
+    (&[(4, b"ftypmif1")], "image/heif"),
+    (&[(4, b"ftypM4A ")], "audio/mp4"),
+    (&[(4, b"ftypqt  ")], "video/quicktime"),
+    (&[(4, b"ftyp3gp")], "video/3gpp"),
+    (&[(4, b"ftypisom")], "video/mp4"),
+    (&[(4, b"ftypiso2")], "video/mp4"),
+    (&[(4, b"ftypmp41")], "video/mp4"),
+    (&[(4, b"ftypmp42")], "video/mp4"),
+    (&[(4, b"ftypavc1")], "video/mp4"),
+    (&[(4, b"ftypM4V ")], "video/mp4"),
+    (&[(4, b"ftypdash")], "video/mp4"),
+    (&[(0, b"RIFF"), (8, b"WAVE")], "audio/wav"),
+    (&[(0, b"OggS")], "audio/ogg"),
+    (&[(0, b"fLaC")], "audio/flac"),
+    (&[(0, b"ID3")], "audio/mpeg"),
+];
+
+/// Types of archives and compressed files, which often hold formats with a more specific type.
+const CONTAINERS: &[&str] = &[
+    "application/zip",
+    "application/gzip",
+    "application/x-7z-compressed",
+    "application/x-tar",
+];
+
+/// Start of an EBML document, such as a Matroska or WebM file.
+const EBML_MAGIC: &[u8] = b"\x1a\x45\xdf\xa3";
+
+/// ID of the EBML element naming the document type.
+const EBML_DOC_TYPE: &[u8] = b"\x42\x82";
+
+/// Returns the MIME type identified by the signature at the start of `prefix`, if any.
+pub(crate) fn sniff(prefix: &[u8]) -> Option<Mime> {
+    let mime = if prefix.starts_with(EBML_MAGIC) {
+        ebml_type(prefix)?
+    } else {
+        SIGNATURES
+            .iter()
+            .find(|(parts, _)| {
+                parts.iter().all(|&(offset, magic)| {
+                    prefix.get(offset..offset + magic.len()) == Some(magic)
+                })
+            })
+            .map(|&(_, mime)| mime)?
+    };
+
+    mime.parse().ok()
+}
+
+/// Returns the type of an EBML document from its `DocType` element.
+fn ebml_type(prefix: &[u8]) -> Option<&'static str> {
+    let pos = prefix
+        .windows(EBML_DOC_TYPE.len())
+        .position(|window| window == EBML_DOC_TYPE)?;
+
+    // the element size is a variable length integer, which takes one byte for short strings
+    let size = *prefix.get(pos + EBML_DOC_TYPE.len())?;
+    if size & 0x80 == 0 {
+        return None;
+    }
+
+    let start = pos + EBML_DOC_TYPE.len() + 1;
+    match prefix.get(start..start + usize::from(size & 0x7f))? {
+        b"webm" => Some("video/webm"),
+        b"matroska" => Some("video/x-matroska"),
+        _ => None,
+    }
+}
+
+/// Returns the type detected from `prefix` if it should replace `guessed`, the type guessed from
+/// the file's extension.
+///
+/// A detected type is only used when nothing specific was guessed, or when it belongs to another
+/// top-level type, such as an image named like a text file. Archives are never detected in place
+/// of a more specific type, since many formats (e.g. `.docx` or `.jar` files) are archives.
+pub(crate) fn detect(guessed: &Mime, prefix: &[u8]) -> Option<Mime> {
+    let detected = sniff(prefix)?;
+
+    if *guessed == mime::APPLICATION_OCTET_STREAM {
+        Some(detected)
+    } else if CONTAINERS.contains(&detected.essence_str())
+        || detected.type_() == guessed.type_()
+    {
+        None
+    } else {
+        Some(detected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        let sniff = |prefix: &[u8]| sniff(prefix).map(|mime| mime.to_string());
+
+        assert_eq!(
+            sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap(),
+            "image/png"
+        );
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 ").unwrap(), "image/webp");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt ").unwrap(), "audio/wav");
+        assert_eq!(sniff(b"\0asm\x01\0\0\0").unwrap(), "application/wasm");
+
+        let mut tar = vec![0; SNIFF_LEN];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(sniff(&tar).unwrap(), "application/x-tar");
+
+        assert!(sniff(b"RIFF\0\0\0\0").is_none());
+        assert!(sniff(b"<!DOCTYPE html>").is_none());
+        assert!(sniff(b"").is_none());
+
+        // ISO base media files by major brand
+        assert_eq!(
+            sniff(b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf").unwrap(),
+            "image/avif"
+        );
+        assert_eq!(sniff(b"\0\0\0\x20ftypisom\0\0\x02\0").unwrap(), "video/mp4");
+        assert_eq!(sniff(b"\0\0\0\x20ftypM4A \0\0\0\0").unwrap(), "audio/mp4");
+        assert!(sniff(b"\0\0\0\x18ftypcrx \0\0\0\x01").is_none());
+
+        // EBML documents by doc type
+        assert_eq!(
+            sniff(b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01\x42\x82\x88matroska").unwrap(),
+            "video/x-matroska"
+        );
+        assert_eq!(
+            sniff(b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01\x42\x82\x84webm").unwrap(),
+            "video/webm"
+        );
+        assert!(sniff(b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01").is_none());
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |guessed: &str, prefix: &[u8]| {
+            detect(&guessed.parse().unwrap(), prefix).map(|mime| mime.to_string())
+        };
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let docx = b"PK\x03\x04\x14\0\x06\0\x08\0\0\0!\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x13\0\0\0[Content_Types].xml";
+
+        assert_eq!(
+            detect("application/octet-stream", png).unwrap(),
+            "image/png"
+        );
+        assert_eq!(detect("text/plain", png).unwrap(), "image/png");
+        assert!(detect("image/x-icon", png).is_none());
+
+        // archives do not replace the types of formats built on them
+        let docx_type =
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        assert!(detect(docx_type, docx).is_none());
+        assert!(detect("image/svg+xml", b"\x1f\x8b\x08\0").is_none());
+        assert_eq!(
+            detect("application/octet-stream", docx).unwrap(),
+            "application/zip"
+        );
+    }
+}